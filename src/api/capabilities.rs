@@ -0,0 +1,327 @@
+//! Capability traits implemented by [`ZygiskApi`] for every API version that supports the feature.
+//!
+//! These allow code to be written once for any API version providing a given capability:
+//!
+//! ```
+//! use std::os::fd::RawFd;
+//!
+//! use zygisk_api::api::{HasModuleDir, V2, V5, ZygiskApi};
+//!
+//! fn module_dir(api: &impl HasModuleDir) -> Option<RawFd> {
+//!     match api.get_module_dir() {
+//!         -1 => None,
+//!         fd => Some(fd),
+//!     }
+//! }
+//!
+//! fn on_any_version(v2: ZygiskApi<'_, V2>, v5: ZygiskApi<'_, V5>) {
+//!     let _ = (module_dir(&v2), module_dir(&v5));
+//! }
+//! ```
+
+use core::ffi::CStr;
+use std::os::{
+    fd::{AsFd, OwnedFd, RawFd},
+    unix::net::UnixStream,
+};
+
+use jni::{JNIEnv, strings::JNIStr, sys::JNINativeMethod};
+use libc::{dev_t, ino_t};
+
+use super::{V1, V2, V3, V4, V5, ZygiskApi, v1::ZygiskOption, v2::StateFlags};
+use crate::{error::ZygiskError, impl_sealing::Sealed, raw::ZygiskRaw};
+
+impl<'a, Version> Sealed for ZygiskApi<'a, Version> where Version: ZygiskRaw<'a> + 'a {}
+
+/// Connecting to the module's root companion process
+pub trait HasCompanion: Sealed {
+    /// See [`ZygiskApi::<V1>::with_companion`](ZygiskApi#method.with_companion).
+    fn with_companion<R>(&mut self, f: impl FnOnce(&mut UnixStream) -> R)
+    -> Result<R, ZygiskError>;
+}
+
+/// Setting module options
+pub trait HasOptions: Sealed {
+    /// See [`ZygiskApi::<V1>::set_option`](ZygiskApi#method.set_option).
+    fn set_option(&mut self, option: ZygiskOption);
+}
+
+/// Querying the state flags of the current process
+pub trait HasFlags: Sealed {
+    /// Returns [`ZygiskError::UnrecognizedStateFlag`] if Zygisk reported flags unknown to this crate.
+    fn get_flags(&self) -> Result<StateFlags, ZygiskError>;
+}
+
+/// Accessing the module's root directory
+pub trait HasModuleDir: Sealed {
+    /// Returns a file descriptor of the module's root directory, or `-1` on failure.
+    fn get_module_dir(&self) -> RawFd;
+}
+
+/// Hooking JNI native methods
+pub trait HasJniHooks: Sealed {
+    /// See [`ZygiskApi::<V1>::hook_jni_native_methods`](ZygiskApi#method.hook_jni_native_methods).
+    ///
+    /// # Safety
+    ///
+    /// A badly designed hook or misuse of raw pointers may lead to memory unsafety.
+    unsafe fn hook_jni_native_methods(
+        &mut self,
+        env: JNIEnv,
+        class_name: &JNIStr,
+        methods: &mut [JNINativeMethod],
+    );
+}
+
+/// Committing registered PLT hooks
+pub trait HasPltHooks: Sealed {
+    /// Commit all the hooks that was previously registered.
+    ///
+    /// Returns [`ZygiskError::PltHookCommitError`] if any error occurs.
+    fn plt_hook_commit(&mut self) -> Result<(), ZygiskError>;
+}
+
+/// Registering PLT hooks on ELFs matched by a path regex (V1 to V3)
+pub trait HasRegexPltHooks: HasPltHooks {
+    /// See [`ZygiskApi::<V1>::plt_hook_register`](ZygiskApi#method.plt_hook_register).
+    ///
+    /// # Safety
+    ///
+    /// A badly designed hook or misuse of raw pointers may lead to memory unsafety.
+    /// `old_func` must stay valid until the hooks are committed.
+    unsafe fn plt_hook_register(
+        &mut self,
+        regex: &CStr,
+        symbol: &CStr,
+        new_func: *const (),
+        old_func: &mut *const (),
+    );
+
+    /// For ELFs loaded in memory matching `regex`, exclude hooks registered for `symbol`.
+    /// If symbol is empty, then all symbols will be excluded.
+    ///
+    /// # Safety
+    ///
+    /// See [`HasRegexPltHooks::plt_hook_register`].
+    unsafe fn plt_hook_exclude(&mut self, regex: &CStr, symbol: &CStr);
+}
+
+/// Registering PLT hooks on ELFs identified by their device and inode numbers (V4 onwards)
+pub trait HasInodePltHooks: HasPltHooks {
+    /// For ELFs loaded in memory matching the `device` and `inode` pair, replace function `symbol`
+    /// with `replacement`. The original function pointer will be saved to `original`.
+    ///
+    /// # Safety
+    ///
+    /// A badly designed hook or misuse of raw pointers may lead to memory unsafety.
+    /// `original` must stay valid until the hooks are committed.
+    unsafe fn plt_hook_register(
+        &mut self,
+        device: dev_t,
+        inode: ino_t,
+        symbol: &CStr,
+        replacement: *const (),
+        original: &mut *const (),
+    );
+}
+
+/// Keeping file descriptors open across specialization (V4 onwards)
+pub trait HasFdExemption: Sealed {
+    /// See [`ZygiskApi::<V4>::exempt_fd`](ZygiskApi#method.exempt_fd).
+    fn exempt_fd(&mut self, fd: impl AsFd) -> Result<(), ZygiskError>;
+
+    /// See [`ZygiskApi::<V4>::exempt_owned_fd`](ZygiskApi#method.exempt_owned_fd).
+    fn exempt_owned_fd(&mut self, fd: OwnedFd) -> Result<OwnedFd, ZygiskError>;
+}
+
+/// Implements a capability trait for the listed API versions. The methods forward to the inherent
+/// methods of the same name, which `Self::` paths resolve to first.
+macro_rules! impl_capability {
+    ($capability:ident for [$($version:ident),+] $items:tt) => {
+        $(
+            impl $capability for ZygiskApi<'_, $version> $items
+        )+
+    };
+}
+
+impl_capability!(HasCompanion for [V1, V2, V3, V4, V5] {
+    #[inline(always)]
+    fn with_companion<R>(
+        &mut self,
+        f: impl FnOnce(&mut UnixStream) -> R,
+    ) -> Result<R, ZygiskError> {
+        Self::with_companion(self, f)
+    }
+});
+
+impl_capability!(HasOptions for [V1, V2, V3, V4, V5] {
+    #[inline(always)]
+    fn set_option(&mut self, option: ZygiskOption) {
+        Self::set_option(self, option)
+    }
+});
+
+impl_capability!(HasFlags for [V2, V3, V4, V5] {
+    #[inline(always)]
+    fn get_flags(&self) -> Result<StateFlags, ZygiskError> {
+        Self::get_flags(self)
+    }
+});
+
+impl_capability!(HasModuleDir for [V2, V3, V4, V5] {
+    #[inline(always)]
+    fn get_module_dir(&self) -> RawFd {
+        Self::get_module_dir(self)
+    }
+});
+
+impl_capability!(HasJniHooks for [V1, V2, V3, V4, V5] {
+    #[inline(always)]
+    unsafe fn hook_jni_native_methods(
+        &mut self,
+        env: JNIEnv,
+        class_name: &JNIStr,
+        methods: &mut [JNINativeMethod],
+    ) {
+        unsafe { Self::hook_jni_native_methods(self, env, class_name, methods) }
+    }
+});
+
+impl_capability!(HasPltHooks for [V1, V2, V3, V4, V5] {
+    #[inline(always)]
+    fn plt_hook_commit(&mut self) -> Result<(), ZygiskError> {
+        Self::plt_hook_commit(self)
+    }
+});
+
+impl_capability!(HasRegexPltHooks for [V1, V2, V3] {
+    #[inline(always)]
+    unsafe fn plt_hook_register(
+        &mut self,
+        regex: &CStr,
+        symbol: &CStr,
+        new_func: *const (),
+        old_func: &mut *const (),
+    ) {
+        unsafe { Self::plt_hook_register(self, regex, symbol, new_func, old_func) }
+    }
+
+    // The inherent method is only unsafe from V2 onwards
+    #[inline(always)]
+    #[allow(unused_unsafe)]
+    unsafe fn plt_hook_exclude(&mut self, regex: &CStr, symbol: &CStr) {
+        unsafe { Self::plt_hook_exclude(self, regex, symbol) }
+    }
+});
+
+impl_capability!(HasInodePltHooks for [V4, V5] {
+    #[inline(always)]
+    unsafe fn plt_hook_register(
+        &mut self,
+        device: dev_t,
+        inode: ino_t,
+        symbol: &CStr,
+        replacement: *const (),
+        original: &mut *const (),
+    ) {
+        unsafe { Self::plt_hook_register(self, device, inode, symbol, replacement, original) }
+    }
+});
+
+impl_capability!(HasFdExemption for [V4, V5] {
+    #[inline(always)]
+    fn exempt_fd(&mut self, fd: impl AsFd) -> Result<(), ZygiskError> {
+        Self::exempt_fd(self, fd)
+    }
+
+    #[inline(always)]
+    fn exempt_owned_fd(&mut self, fd: OwnedFd) -> Result<OwnedFd, ZygiskError> {
+        Self::exempt_owned_fd(self, fd)
+    }
+});
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use std::{
+        io::Read,
+        os::{fd::AsRawFd, unix::net::UnixStream},
+    };
+
+    use super::{HasFdExemption, HasFlags, HasModuleDir, HasOptions};
+    use crate::{
+        api::{V2, V3, V4, V5, v1::ZygiskOption, v2::StateFlags},
+        error::ZygiskError,
+        testing::{Call, MockApi, MockHost},
+    };
+
+    fn unload_if_denylisted(api: &mut (impl HasFlags + HasOptions + HasModuleDir)) {
+        if api.get_module_dir() == -1
+            && api
+                .get_flags()
+                .is_ok_and(|flags| flags.contains(StateFlags::PROCESS_ON_DENYLIST))
+        {
+            api.set_option(ZygiskOption::DlCloseModuleLibrary);
+        }
+    }
+
+    fn check<V>() -> Vec<Call>
+    where
+        V: MockApi,
+        for<'a> crate::api::ZygiskApi<'a, V>: HasFlags + HasOptions + HasModuleDir,
+    {
+        let mut host = MockHost::<V>::new();
+        host.set_flags(StateFlags::PROCESS_ON_DENYLIST.bits());
+        unload_if_denylisted(&mut host.api());
+        host.calls()
+    }
+
+    #[test]
+    fn generic_over_versions() {
+        let expected = [
+            Call::GetModuleDir,
+            Call::GetFlags,
+            Call::SetOption(ZygiskOption::DlCloseModuleLibrary),
+        ];
+
+        assert_eq!(check::<V2>(), expected);
+        assert_eq!(check::<V3>(), expected);
+        assert_eq!(check::<V4>(), expected);
+        assert_eq!(check::<V5>(), expected);
+    }
+
+    #[test]
+    fn exempts_fds() {
+        let (sock, _peer) = UnixStream::pair().unwrap();
+        let raw = sock.as_raw_fd();
+
+        let mut host = MockHost::<V5>::new();
+        let sock = host.api().exempt_owned_fd(sock.into()).unwrap();
+        assert_eq!(sock.as_raw_fd(), raw);
+
+        host.set_exempt_fd_result(false);
+        assert!(matches!(
+            HasFdExemption::exempt_fd(&mut host.api(), &sock),
+            Err(ZygiskError::ExemptFdError(fd)) if fd == raw
+        ));
+
+        // A refused owned fd is closed rather than leaked
+        let (sock, mut peer) = UnixStream::pair().unwrap();
+        let refused = sock.as_raw_fd();
+        assert!(matches!(
+            host.api().exempt_owned_fd(sock.into()),
+            Err(ZygiskError::ExemptFdError(fd)) if fd == refused
+        ));
+        assert_eq!(peer.read(&mut [0; 1]).unwrap(), 0);
+
+        assert_eq!(
+            host.calls(),
+            [
+                Call::ExemptFd(raw),
+                Call::ExemptFd(raw),
+                Call::ExemptFd(refused)
+            ]
+        );
+    }
+}
@@ -1,5 +1,10 @@
+use libc::c_long;
+
 use crate::raw::{ApiTableRef, ZygiskRaw};
 
+mod capabilities;
+pub use capabilities::*;
+
 pub mod v1;
 pub use v1::V1;
 
@@ -29,4 +34,10 @@ where
     pub unsafe fn dispatch(&self) -> &<Version as ZygiskRaw<'a>>::ApiTable {
         unsafe { &*self.0.0 }
     }
+
+    /// Returns the API version that the module has been registered with.
+    #[inline(always)]
+    pub const fn api_version(&self) -> c_long {
+        <Version as ZygiskRaw<'a>>::API_VERSION
+    }
 }
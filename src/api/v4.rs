@@ -1,6 +1,6 @@
 use core::{ffi, mem, ops::Deref, ptr::NonNull};
 use std::os::{
-    fd::{FromRawFd, RawFd},
+    fd::{AsFd, AsRawFd, FromRawFd, OwnedFd, RawFd},
     unix::net::UnixStream,
 };
 
@@ -81,6 +81,13 @@ impl super::ZygiskApi<'_, V4> {
         };
     }
 
+    /// Hook functions in the PLT (Procedure Linkage Table) of ELFs loaded in memory.
+    ///
+    /// For ELFs loaded in memory matching the `device` and `inode` pair, replace function `symbol`
+    /// with `replacement`. The original function pointer will be saved to `original` once the hooks
+    /// are committed. [`MemoryMap`](crate::maps::MemoryMap) can be used to look up the pair of a
+    /// mapped file.
+    ///
     /// # Safety
     ///
     #[inline(always)]
@@ -114,6 +121,31 @@ impl super::ZygiskApi<'_, V4> {
         }
     }
 
+    /// Exempt the file descriptor `fd` from being automatically closed by zygote.
+    ///
+    /// This API only makes sense in `pre_app_specialize`; calling this method in any other situation
+    /// is either a no-op (returns `Ok`) or an error.
+    ///
+    /// Returns [`ZygiskError::ExemptFdError`] if Zygisk refused the request.
+    #[inline(always)]
+    pub fn exempt_fd(&mut self, fd: impl AsFd) -> Result<(), ZygiskError> {
+        let fd = fd.as_fd().as_raw_fd();
+
+        match (unsafe { self.dispatch() }.exempt_fd_fn)(fd) {
+            true => Ok(()),
+            false => Err(ZygiskError::ExemptFdError(fd)),
+        }
+    }
+
+    /// Exempt an owned file descriptor from being automatically closed by zygote, handing it back
+    /// on success so that it can be kept until `post_app_specialize`.
+    ///
+    /// If Zygisk refused the request, the file descriptor is closed.
+    #[inline(always)]
+    pub fn exempt_owned_fd(&mut self, fd: OwnedFd) -> Result<OwnedFd, ZygiskError> {
+        self.exempt_fd(&fd).map(|()| fd)
+    }
+
     #[inline(always)]
     pub fn plt_hook_commit(&mut self) -> Result<(), ZygiskError> {
         match unsafe { (self.dispatch().plt_hook_commit_fn)() } {
@@ -0,0 +1,148 @@
+use core::fmt;
+
+use jni::sys::jlong;
+
+use crate::api::v1::ServerSpecializeArgs;
+
+bitflags::bitflags! {
+    /// A set of Linux capabilities, as in the capability arguments of [`ServerSpecializeArgs`]
+    ///
+    /// Bit `n` stands for the capability numbered `n` in `linux/capability.h`. Both [`Debug`] and
+    /// [`Display`](fmt::Display) list the capabilities by name:
+    ///
+    /// ```
+    /// use zygisk_api::args::Capabilities;
+    ///
+    /// let caps = Capabilities::from(0x60);
+    /// assert_eq!(caps, Capabilities::CAP_KILL | Capabilities::CAP_SETGID);
+    /// assert_eq!(caps.to_string(), "CAP_KILL | CAP_SETGID");
+    /// ```
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct Capabilities: u64 {
+        const CAP_CHOWN = (1 << 0);
+        const CAP_DAC_OVERRIDE = (1 << 1);
+        const CAP_DAC_READ_SEARCH = (1 << 2);
+        const CAP_FOWNER = (1 << 3);
+        const CAP_FSETID = (1 << 4);
+        const CAP_KILL = (1 << 5);
+        const CAP_SETGID = (1 << 6);
+        const CAP_SETUID = (1 << 7);
+        const CAP_SETPCAP = (1 << 8);
+        const CAP_LINUX_IMMUTABLE = (1 << 9);
+        const CAP_NET_BIND_SERVICE = (1 << 10);
+        const CAP_NET_BROADCAST = (1 << 11);
+        const CAP_NET_ADMIN = (1 << 12);
+        const CAP_NET_RAW = (1 << 13);
+        const CAP_IPC_LOCK = (1 << 14);
+        const CAP_IPC_OWNER = (1 << 15);
+        const CAP_SYS_MODULE = (1 << 16);
+        const CAP_SYS_RAWIO = (1 << 17);
+        const CAP_SYS_CHROOT = (1 << 18);
+        const CAP_SYS_PTRACE = (1 << 19);
+        const CAP_SYS_PACCT = (1 << 20);
+        const CAP_SYS_ADMIN = (1 << 21);
+        const CAP_SYS_BOOT = (1 << 22);
+        const CAP_SYS_NICE = (1 << 23);
+        const CAP_SYS_RESOURCE = (1 << 24);
+        const CAP_SYS_TIME = (1 << 25);
+        const CAP_SYS_TTY_CONFIG = (1 << 26);
+        const CAP_MKNOD = (1 << 27);
+        const CAP_LEASE = (1 << 28);
+        const CAP_AUDIT_WRITE = (1 << 29);
+        const CAP_AUDIT_CONTROL = (1 << 30);
+        const CAP_SETFCAP = (1 << 31);
+        const CAP_MAC_OVERRIDE = (1 << 32);
+        const CAP_MAC_ADMIN = (1 << 33);
+        const CAP_SYSLOG = (1 << 34);
+        const CAP_WAKE_ALARM = (1 << 35);
+        const CAP_BLOCK_SUSPEND = (1 << 36);
+        const CAP_AUDIT_READ = (1 << 37);
+        const CAP_PERFMON = (1 << 38);
+        const CAP_BPF = (1 << 39);
+        const CAP_CHECKPOINT_RESTORE = (1 << 40);
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        bitflags::parser::to_writer(self, f)
+    }
+}
+
+impl From<jlong> for Capabilities {
+    #[inline]
+    fn from(capabilities: jlong) -> Self {
+        Self::from_bits_retain(capabilities as u64)
+    }
+}
+
+impl From<Capabilities> for jlong {
+    #[inline]
+    fn from(capabilities: Capabilities) -> Self {
+        capabilities.bits() as jlong
+    }
+}
+
+/// Capability sets of the system server
+///
+/// Changes made in [`ZygiskModule::pre_server_specialize`](crate::ZygiskModule::pre_server_specialize)
+/// apply to the system server once it is forked.
+impl ServerSpecializeArgs<'_> {
+    #[inline]
+    pub fn permitted(&self) -> Capabilities {
+        Capabilities::from(*self.permitted_capabilities)
+    }
+
+    #[inline]
+    pub fn effective(&self) -> Capabilities {
+        Capabilities::from(*self.effective_capabilities)
+    }
+
+    /// Returns whether all of `capabilities` are in the effective set.
+    #[inline]
+    pub fn has_capabilities(&self, capabilities: Capabilities) -> bool {
+        self.effective().contains(capabilities)
+    }
+
+    /// Adds `capabilities` to both the permitted and the effective set.
+    #[inline]
+    pub fn grant_capabilities(&mut self, capabilities: Capabilities) {
+        *self.permitted_capabilities = (self.permitted() | capabilities).into();
+        *self.effective_capabilities = (self.effective() | capabilities).into();
+    }
+
+    /// Removes `capabilities` from both the permitted and the effective set.
+    #[inline]
+    pub fn drop_capabilities(&mut self, capabilities: Capabilities) {
+        *self.permitted_capabilities = (self.permitted() - capabilities).into();
+        *self.effective_capabilities = (self.effective() - capabilities).into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::ToString;
+
+    use super::Capabilities;
+    use crate::testing::FakeServerArgs;
+
+    #[test]
+    fn adjusts_server_capabilities() {
+        let mut fake = FakeServerArgs {
+            permitted_capabilities: 0x1_0000_0060,
+            effective_capabilities: 0x60,
+            ..FakeServerArgs::default()
+        };
+
+        let mut args = fake.args();
+        assert!(args.has_capabilities(Capabilities::CAP_KILL | Capabilities::CAP_SETGID));
+        assert!(!args.has_capabilities(Capabilities::CAP_MAC_OVERRIDE));
+
+        args.drop_capabilities(Capabilities::CAP_KILL | Capabilities::CAP_MAC_OVERRIDE);
+        args.grant_capabilities(Capabilities::CAP_SYS_PTRACE);
+        assert_eq!(args.effective().to_string(), "CAP_SETGID | CAP_SYS_PTRACE");
+
+        assert_eq!(fake.permitted_capabilities, 0x80040);
+        assert_eq!(fake.effective_capabilities, 0x80040);
+    }
+}
@@ -0,0 +1,569 @@
+use jni::{
+    objects::{JIntArray, JObjectArray, JString},
+    sys::{JNI_FALSE, JNI_TRUE, jboolean, jint, jintArray, jlong, jobjectArray},
+};
+
+use crate::{
+    api::{v1, v3, v5},
+    error::ZygiskError,
+    impl_sealing::Sealed,
+};
+
+/// Access to the app specialization arguments of any API version
+///
+/// Implemented by the `AppSpecializeArgs` struct of every API version, so that code handling the
+/// arguments can be shared between versions. Arguments that do not exist in a version's layout
+/// are returned as [`None`], just like optional arguments that zygote did not pass.
+///
+/// The setters take `&mut self`, so they are only available in
+/// [`ZygiskModule::pre_app_specialize`](crate::ZygiskModule::pre_app_specialize), before the
+/// arguments are used. Setting an argument that does not exist in the version's layout returns
+/// [`ZygiskError::UnavailableArgument`], and setting an optional argument that zygote did not
+/// pass returns [`ZygiskError::MissingArgument`].
+///
+/// ```
+/// use core::marker::PhantomData;
+///
+/// use jni::JNIEnv;
+/// use zygisk_api::{ZygiskModule, api::ZygiskApi, args::AnyAppSpecializeArgs, raw::ZygiskRaw};
+///
+/// struct MyModule<V>(PhantomData<V>);
+///
+/// impl<V: for<'a> ZygiskRaw<'a>> ZygiskModule for MyModule<V> {
+///     type Api = V;
+///     type State = ();
+///
+///     fn pre_app_specialize<'a>(
+///         &self,
+///         _: ZygiskApi<'a, V>,
+///         env: JNIEnv<'a>,
+///         args: &'a mut <V as ZygiskRaw<'a>>::AppSpecializeArgs,
+///         _: &mut (),
+///     ) {
+///         let is_target = unsafe { env.get_string_unchecked(args.nice_name()) }
+///             .is_ok_and(|name| name.to_bytes() == b"com.example.app");
+///
+///         if is_target {
+///             let _ = args.set_mount_storage_dirs(false);
+///             if let Ok(name) = env.new_string("com.example.app:renamed") {
+///                 args.set_nice_name(name);
+///             }
+///         }
+///     }
+/// }
+/// ```
+pub trait AnyAppSpecializeArgs<'a>: Sealed {
+    fn uid(&self) -> jint;
+    fn gid(&self) -> jint;
+    fn gids(&self) -> jintArray;
+    fn runtime_flags(&self) -> jint;
+    /// Only available from API v3 onwards.
+    fn rlimits(&self) -> Option<jobjectArray>;
+    fn mount_external(&self) -> jint;
+    fn se_info(&self) -> &JString<'a>;
+    fn nice_name(&self) -> &JString<'a>;
+    fn instruction_set(&self) -> &JString<'a>;
+    fn app_data_dir(&self) -> &JString<'a>;
+
+    /// Only available from API v3 onwards.
+    fn fds_to_ignore(&self) -> Option<jintArray>;
+    fn is_child_zygote(&self) -> Option<jboolean>;
+    fn is_top_app(&self) -> Option<jboolean>;
+    fn pkg_data_info_list(&self) -> Option<jobjectArray>;
+    fn whitelisted_data_info_list(&self) -> Option<jobjectArray>;
+    fn mount_data_dirs(&self) -> Option<jboolean>;
+    fn mount_storage_dirs(&self) -> Option<jboolean>;
+    /// Only available from API v5 onwards.
+    fn mount_sysprop_overrides(&self) -> Option<jboolean>;
+
+    fn set_uid(&mut self, uid: jint);
+    fn set_gid(&mut self, gid: jint);
+    fn set_gids(&mut self, gids: JIntArray<'a>);
+    fn set_runtime_flags(&mut self, runtime_flags: jint);
+    fn set_rlimits(&mut self, rlimits: JObjectArray<'a>) -> Result<(), ZygiskError>;
+    fn set_mount_external(&mut self, mount_external: jint);
+    fn set_se_info(&mut self, se_info: JString<'a>);
+    fn set_nice_name(&mut self, nice_name: JString<'a>);
+    fn set_instruction_set(&mut self, instruction_set: JString<'a>);
+    fn set_app_data_dir(&mut self, app_data_dir: JString<'a>);
+
+    fn set_fds_to_ignore(&mut self, fds_to_ignore: JIntArray<'a>) -> Result<(), ZygiskError>;
+    fn set_is_child_zygote(&mut self, is_child_zygote: bool) -> Result<(), ZygiskError>;
+    fn set_is_top_app(&mut self, is_top_app: bool) -> Result<(), ZygiskError>;
+    fn set_pkg_data_info_list(&mut self, list: JObjectArray<'a>) -> Result<(), ZygiskError>;
+    fn set_whitelisted_data_info_list(&mut self, list: JObjectArray<'a>)
+    -> Result<(), ZygiskError>;
+    fn set_mount_data_dirs(&mut self, mount_data_dirs: bool) -> Result<(), ZygiskError>;
+    fn set_mount_storage_dirs(&mut self, mount_storage_dirs: bool) -> Result<(), ZygiskError>;
+    fn set_mount_sysprop_overrides(
+        &mut self,
+        mount_sysprop_overrides: bool,
+    ) -> Result<(), ZygiskError>;
+}
+
+#[inline]
+fn to_jboolean(value: bool) -> jboolean {
+    if value { JNI_TRUE } else { JNI_FALSE }
+}
+
+/// Overwrite an optional argument, if zygote passed it.
+#[inline]
+fn set_optional<T>(
+    field: &mut Option<&mut T>,
+    name: &'static str,
+    value: T,
+) -> Result<(), ZygiskError> {
+    match field {
+        Some(field) => {
+            **field = value;
+            Ok(())
+        }
+        None => Err(ZygiskError::MissingArgument(name)),
+    }
+}
+
+macro_rules! impl_any_app_specialize_args {
+    ($ty:ident, { $($extra:tt)* }) => {
+        impl Sealed for $ty::AppSpecializeArgs<'_> {}
+
+        impl<'a> AnyAppSpecializeArgs<'a> for $ty::AppSpecializeArgs<'a> {
+            #[inline]
+            fn uid(&self) -> jint {
+                *self.uid
+            }
+
+            #[inline]
+            fn gid(&self) -> jint {
+                *self.gid
+            }
+
+            #[inline]
+            fn gids(&self) -> jintArray {
+                *self.gids
+            }
+
+            #[inline]
+            fn runtime_flags(&self) -> jint {
+                *self.runtime_flags
+            }
+
+            #[inline]
+            fn mount_external(&self) -> jint {
+                *self.mount_external
+            }
+
+            #[inline]
+            fn se_info(&self) -> &JString<'a> {
+                self.se_info
+            }
+
+            #[inline]
+            fn nice_name(&self) -> &JString<'a> {
+                self.nice_name
+            }
+
+            #[inline]
+            fn instruction_set(&self) -> &JString<'a> {
+                self.instruction_set
+            }
+
+            #[inline]
+            fn app_data_dir(&self) -> &JString<'a> {
+                self.app_data_dir
+            }
+
+            #[inline]
+            fn is_child_zygote(&self) -> Option<jboolean> {
+                self.is_child_zygote.as_deref().copied()
+            }
+
+            #[inline]
+            fn is_top_app(&self) -> Option<jboolean> {
+                self.is_top_app.as_deref().copied()
+            }
+
+            #[inline]
+            fn pkg_data_info_list(&self) -> Option<jobjectArray> {
+                self.pkg_data_info_list.as_deref().copied()
+            }
+
+            #[inline]
+            fn whitelisted_data_info_list(&self) -> Option<jobjectArray> {
+                self.whitelisted_data_info_list.as_deref().copied()
+            }
+
+            #[inline]
+            fn mount_data_dirs(&self) -> Option<jboolean> {
+                self.mount_data_dirs.as_deref().copied()
+            }
+
+            #[inline]
+            fn mount_storage_dirs(&self) -> Option<jboolean> {
+                self.mount_storage_dirs.as_deref().copied()
+            }
+
+            #[inline]
+            fn set_uid(&mut self, uid: jint) {
+                *self.uid = uid;
+            }
+
+            #[inline]
+            fn set_gid(&mut self, gid: jint) {
+                *self.gid = gid;
+            }
+
+            #[inline]
+            fn set_gids(&mut self, gids: JIntArray<'a>) {
+                *self.gids = gids.into_raw();
+            }
+
+            #[inline]
+            fn set_runtime_flags(&mut self, runtime_flags: jint) {
+                *self.runtime_flags = runtime_flags;
+            }
+
+            #[inline]
+            fn set_mount_external(&mut self, mount_external: jint) {
+                *self.mount_external = mount_external;
+            }
+
+            #[inline]
+            fn set_se_info(&mut self, se_info: JString<'a>) {
+                *self.se_info = se_info;
+            }
+
+            #[inline]
+            fn set_nice_name(&mut self, nice_name: JString<'a>) {
+                *self.nice_name = nice_name;
+            }
+
+            #[inline]
+            fn set_instruction_set(&mut self, instruction_set: JString<'a>) {
+                *self.instruction_set = instruction_set;
+            }
+
+            #[inline]
+            fn set_app_data_dir(&mut self, app_data_dir: JString<'a>) {
+                *self.app_data_dir = app_data_dir;
+            }
+
+            #[inline]
+            fn set_is_child_zygote(&mut self, is_child_zygote: bool) -> Result<(), ZygiskError> {
+                set_optional(&mut self.is_child_zygote, "is_child_zygote", to_jboolean(is_child_zygote))
+            }
+
+            #[inline]
+            fn set_is_top_app(&mut self, is_top_app: bool) -> Result<(), ZygiskError> {
+                set_optional(&mut self.is_top_app, "is_top_app", to_jboolean(is_top_app))
+            }
+
+            #[inline]
+            fn set_pkg_data_info_list(&mut self, list: JObjectArray<'a>) -> Result<(), ZygiskError> {
+                set_optional(&mut self.pkg_data_info_list, "pkg_data_info_list", list.into_raw())
+            }
+
+            #[inline]
+            fn set_whitelisted_data_info_list(
+                &mut self,
+                list: JObjectArray<'a>,
+            ) -> Result<(), ZygiskError> {
+                set_optional(
+                    &mut self.whitelisted_data_info_list,
+                    "whitelisted_data_info_list",
+                    list.into_raw(),
+                )
+            }
+
+            #[inline]
+            fn set_mount_data_dirs(&mut self, mount_data_dirs: bool) -> Result<(), ZygiskError> {
+                set_optional(&mut self.mount_data_dirs, "mount_data_dirs", to_jboolean(mount_data_dirs))
+            }
+
+            #[inline]
+            fn set_mount_storage_dirs(&mut self, mount_storage_dirs: bool) -> Result<(), ZygiskError> {
+                set_optional(
+                    &mut self.mount_storage_dirs,
+                    "mount_storage_dirs",
+                    to_jboolean(mount_storage_dirs),
+                )
+            }
+
+            $($extra)*
+        }
+    };
+}
+
+impl_any_app_specialize_args!(v1, {
+    #[inline]
+    fn rlimits(&self) -> Option<jobjectArray> {
+        None
+    }
+
+    #[inline]
+    fn fds_to_ignore(&self) -> Option<jintArray> {
+        None
+    }
+
+    #[inline]
+    fn mount_sysprop_overrides(&self) -> Option<jboolean> {
+        None
+    }
+
+    #[inline]
+    fn set_rlimits(&mut self, _: JObjectArray<'a>) -> Result<(), ZygiskError> {
+        Err(ZygiskError::UnavailableArgument("rlimits"))
+    }
+
+    #[inline]
+    fn set_fds_to_ignore(&mut self, _: JIntArray<'a>) -> Result<(), ZygiskError> {
+        Err(ZygiskError::UnavailableArgument("fds_to_ignore"))
+    }
+
+    #[inline]
+    fn set_mount_sysprop_overrides(&mut self, _: bool) -> Result<(), ZygiskError> {
+        Err(ZygiskError::UnavailableArgument("mount_sysprop_overrides"))
+    }
+});
+
+impl_any_app_specialize_args!(v3, {
+    #[inline]
+    fn rlimits(&self) -> Option<jobjectArray> {
+        Some(*self.rlimits)
+    }
+
+    #[inline]
+    fn fds_to_ignore(&self) -> Option<jintArray> {
+        self.fds_to_ignore.as_deref().copied()
+    }
+
+    #[inline]
+    fn mount_sysprop_overrides(&self) -> Option<jboolean> {
+        None
+    }
+
+    #[inline]
+    fn set_rlimits(&mut self, rlimits: JObjectArray<'a>) -> Result<(), ZygiskError> {
+        *self.rlimits = rlimits.into_raw();
+        Ok(())
+    }
+
+    #[inline]
+    fn set_fds_to_ignore(&mut self, fds_to_ignore: JIntArray<'a>) -> Result<(), ZygiskError> {
+        set_optional(
+            &mut self.fds_to_ignore,
+            "fds_to_ignore",
+            fds_to_ignore.into_raw(),
+        )
+    }
+
+    #[inline]
+    fn set_mount_sysprop_overrides(&mut self, _: bool) -> Result<(), ZygiskError> {
+        Err(ZygiskError::UnavailableArgument("mount_sysprop_overrides"))
+    }
+});
+
+impl_any_app_specialize_args!(v5, {
+    #[inline]
+    fn rlimits(&self) -> Option<jobjectArray> {
+        Some(*self.rlimits)
+    }
+
+    #[inline]
+    fn fds_to_ignore(&self) -> Option<jintArray> {
+        self.fds_to_ignore.as_deref().copied()
+    }
+
+    #[inline]
+    fn mount_sysprop_overrides(&self) -> Option<jboolean> {
+        self.mount_sysprop_overrides.as_deref().copied()
+    }
+
+    #[inline]
+    fn set_rlimits(&mut self, rlimits: JObjectArray<'a>) -> Result<(), ZygiskError> {
+        *self.rlimits = rlimits.into_raw();
+        Ok(())
+    }
+
+    #[inline]
+    fn set_fds_to_ignore(&mut self, fds_to_ignore: JIntArray<'a>) -> Result<(), ZygiskError> {
+        set_optional(
+            &mut self.fds_to_ignore,
+            "fds_to_ignore",
+            fds_to_ignore.into_raw(),
+        )
+    }
+
+    #[inline]
+    fn set_mount_sysprop_overrides(
+        &mut self,
+        mount_sysprop_overrides: bool,
+    ) -> Result<(), ZygiskError> {
+        set_optional(
+            &mut self.mount_sysprop_overrides,
+            "mount_sysprop_overrides",
+            to_jboolean(mount_sysprop_overrides),
+        )
+    }
+});
+
+/// Access to the system server specialization arguments of any API version
+///
+/// Every API version shares the same `ServerSpecializeArgs` layout, but generic code only sees it
+/// through [`ZygiskRaw::ServerSpecializeArgs`](crate::raw::ZygiskRaw::ServerSpecializeArgs). As
+/// with [`AnyAppSpecializeArgs`], the setters are only available in
+/// [`ZygiskModule::pre_server_specialize`](crate::ZygiskModule::pre_server_specialize).
+pub trait AnyServerSpecializeArgs<'a>: Sealed {
+    fn uid(&self) -> jint;
+    fn gid(&self) -> jint;
+    fn gids(&self) -> jintArray;
+    fn runtime_flags(&self) -> jint;
+    fn permitted_capabilities(&self) -> jlong;
+    fn effective_capabilities(&self) -> jlong;
+
+    fn set_uid(&mut self, uid: jint);
+    fn set_gid(&mut self, gid: jint);
+    fn set_gids(&mut self, gids: JIntArray<'a>);
+    fn set_runtime_flags(&mut self, runtime_flags: jint);
+    fn set_permitted_capabilities(&mut self, capabilities: jlong);
+    fn set_effective_capabilities(&mut self, capabilities: jlong);
+}
+
+impl Sealed for v1::ServerSpecializeArgs<'_> {}
+
+impl<'a> AnyServerSpecializeArgs<'a> for v1::ServerSpecializeArgs<'a> {
+    #[inline]
+    fn uid(&self) -> jint {
+        *self.uid
+    }
+
+    #[inline]
+    fn gid(&self) -> jint {
+        *self.gid
+    }
+
+    #[inline]
+    fn gids(&self) -> jintArray {
+        *self.gids
+    }
+
+    #[inline]
+    fn runtime_flags(&self) -> jint {
+        *self.runtime_flags
+    }
+
+    #[inline]
+    fn permitted_capabilities(&self) -> jlong {
+        *self.permitted_capabilities
+    }
+
+    #[inline]
+    fn effective_capabilities(&self) -> jlong {
+        *self.effective_capabilities
+    }
+
+    #[inline]
+    fn set_uid(&mut self, uid: jint) {
+        *self.uid = uid;
+    }
+
+    #[inline]
+    fn set_gid(&mut self, gid: jint) {
+        *self.gid = gid;
+    }
+
+    #[inline]
+    fn set_gids(&mut self, gids: JIntArray<'a>) {
+        *self.gids = gids.into_raw();
+    }
+
+    #[inline]
+    fn set_runtime_flags(&mut self, runtime_flags: jint) {
+        *self.runtime_flags = runtime_flags;
+    }
+
+    #[inline]
+    fn set_permitted_capabilities(&mut self, capabilities: jlong) {
+        *self.permitted_capabilities = capabilities;
+    }
+
+    #[inline]
+    fn set_effective_capabilities(&mut self, capabilities: jlong) {
+        *self.effective_capabilities = capabilities;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jni::objects::JString;
+
+    use super::{AnyAppSpecializeArgs, AnyServerSpecializeArgs};
+    use crate::{
+        error::ZygiskError,
+        testing::{FakeAppArgs, FakeJvm, FakeServerArgs},
+    };
+
+    fn summary<'a>(args: &impl AnyAppSpecializeArgs<'a>) -> (i32, bool, bool) {
+        (
+            args.uid(),
+            args.rlimits().is_some(),
+            args.is_top_app().is_some_and(|top| top != 0),
+        )
+    }
+
+    #[test]
+    fn shared_across_layouts() {
+        let jvm = FakeJvm::new();
+
+        let mut fake = FakeAppArgs::new(&jvm, 10123, "com.example");
+        fake.is_top_app = Some(1);
+        assert_eq!(summary(&fake.v1()), (10123, false, true));
+
+        let mut fake = FakeAppArgs::new(&jvm, 10124, "com.example");
+        assert_eq!(summary(&fake.v3()), (10124, true, false));
+    }
+
+    #[test]
+    fn sets_arguments() {
+        let jvm = FakeJvm::new();
+        let mut fake = FakeAppArgs::new(&jvm, 10123, "com.example");
+        fake.mount_storage_dirs = Some(1);
+
+        let mut args = fake.v5();
+        args.set_uid(10124);
+        args.set_nice_name(unsafe { JString::from_raw(jvm.new_string("com.example:renamed")) });
+        args.set_mount_storage_dirs(false).unwrap();
+        assert!(matches!(
+            args.set_mount_data_dirs(true),
+            Err(ZygiskError::MissingArgument("mount_data_dirs"))
+        ));
+
+        assert_eq!(fake.uid, 10124);
+        assert_eq!(
+            jvm.string(fake.nice_name).as_deref(),
+            Some("com.example:renamed")
+        );
+        assert_eq!(fake.mount_storage_dirs, Some(0));
+
+        let rlimits = unsafe { jni::objects::JObjectArray::from_raw(jvm.new_object_array(&[])) };
+        assert!(matches!(
+            fake.v1().set_rlimits(rlimits),
+            Err(ZygiskError::UnavailableArgument("rlimits"))
+        ));
+    }
+
+    #[test]
+    fn sets_server_arguments() {
+        let jvm = FakeJvm::new();
+        let mut fake = FakeServerArgs::new(&jvm);
+        fake.permitted_capabilities = 0x60;
+
+        let mut args = fake.args();
+        args.set_runtime_flags(0x4);
+        args.set_permitted_capabilities(args.permitted_capabilities() | 0x80000);
+        args.set_effective_capabilities(0x80000);
+
+        assert_eq!(fake.runtime_flags, 0x4);
+        assert_eq!(fake.permitted_capabilities, 0x80060);
+        assert_eq!(fake.effective_capabilities, 0x80000);
+    }
+}
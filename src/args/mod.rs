@@ -0,0 +1,34 @@
+//! Decoded views of the arguments passed to the specialization callbacks.
+//!
+//! The raw `AppSpecializeArgs` and `ServerSpecializeArgs` structs point straight into the
+//! arguments of zygote's JNI methods, so reading them requires JNI calls. The owned snapshots in
+//! this module decode every argument into plain Rust values at once. [`AnyAppSpecializeArgs`]
+//! and [`AnyServerSpecializeArgs`] give access to the arguments of every API version through the
+//! same methods, and
+//! [`RuntimeFlags`], [`MountExternal`] and [`Capabilities`] interpret the `runtime_flags`,
+//! `mount_external` and capability arguments:
+//!
+//! ```no_run
+//! use zygisk_api::{api::V5, args::OwnedAppSpecializeArgs, raw::ZygiskRaw};
+//!
+//! fn is_target(env: &mut jni::JNIEnv<'_>, args: &<V5 as ZygiskRaw<'_>>::AppSpecializeArgs) -> bool {
+//!     OwnedAppSpecializeArgs::new(env, args)
+//!         .is_ok_and(|args| args.nice_name.as_deref() == Some("com.example.app"))
+//! }
+//! ```
+
+mod capabilities;
+pub use capabilities::*;
+
+mod layout;
+pub use layout::*;
+
+mod mount_external;
+pub use mount_external::*;
+
+mod owned;
+pub(crate) use owned::decode_string;
+pub use owned::*;
+
+mod runtime_flags;
+pub use runtime_flags::*;
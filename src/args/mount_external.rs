@@ -0,0 +1,176 @@
+use jni::sys::jint;
+
+/// The `mount_external` specialization argument: the view of external storage the app gets
+///
+/// Values follow `Zygote.MOUNT_EXTERNAL_*`, which mirror vold's remount modes. The numbering was
+/// changed in Android 12 (SDK 31), when the storage sandbox modes were removed, so converting
+/// from and to the raw value requires the SDK version of the device. Values unknown to this
+/// crate are kept as [`Unknown`](MountExternal::Unknown) so that they convert back unchanged.
+///
+/// ```
+/// use zygisk_api::args::MountExternal;
+///
+/// let mode = MountExternal::from_raw(3, 34);
+/// assert_eq!(mode, MountExternal::PassThrough);
+/// assert!(mode.has_full_access());
+///
+/// // On Android 11, 3 is the read-write view of an app with the storage permission
+/// let mode = MountExternal::from_raw(3, 30);
+/// assert_eq!(mode, MountExternal::Write);
+/// assert!(!mode.has_full_access());
+///
+/// assert_eq!(MountExternal::Default.to_raw(34), Some(1));
+/// assert_eq!(MountExternal::Write.to_raw(34), None);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MountExternal {
+    /// No external storage
+    None,
+    /// Storage through FUSE, restricted by scoped storage
+    Default,
+    /// Read-only storage (before Android 12)
+    Read,
+    /// Read-write storage (before Android 12)
+    Write,
+    /// The storage view of apps installed before the storage sandbox was enabled (before Android 12)
+    Legacy,
+    /// Storage with `Android/obb` mounted writable for package installers
+    Installer,
+    /// Read-write storage, without the per-package sandbox (before Android 12)
+    Full,
+    /// Direct access to the lower file system, bypassing FUSE (e.g. the media provider)
+    PassThrough,
+    /// Storage through FUSE, with `Android/data` and `Android/obb` passed through writable
+    AndroidWritable,
+    Unknown(jint),
+}
+
+/// The first SDK version using the Android 12 numbering of the modes
+const SDK_S: u32 = 31;
+
+impl MountExternal {
+    /// Interpret a raw `mount_external` value on a device running the SDK version `sdk`, as in
+    /// `ro.build.version.sdk`.
+    pub fn from_raw(mode: jint, sdk: u32) -> Self {
+        if sdk >= SDK_S {
+            match mode {
+                0 => Self::None,
+                1 => Self::Default,
+                2 => Self::Installer,
+                3 => Self::PassThrough,
+                4 => Self::AndroidWritable,
+                _ => Self::Unknown(mode),
+            }
+        } else {
+            match mode {
+                0 => Self::None,
+                1 => Self::Default,
+                2 => Self::Read,
+                3 => Self::Write,
+                4 => Self::Legacy,
+                5 => Self::Installer,
+                6 => Self::Full,
+                7 => Self::PassThrough,
+                8 => Self::AndroidWritable,
+                _ => Self::Unknown(mode),
+            }
+        }
+    }
+
+    /// Returns the raw value of this mode on a device running the SDK version `sdk`, or [`None`]
+    /// if the mode does not exist there.
+    pub fn to_raw(self, sdk: u32) -> Option<jint> {
+        if sdk >= SDK_S {
+            match self {
+                Self::None => Some(0),
+                Self::Default => Some(1),
+                Self::Installer => Some(2),
+                Self::PassThrough => Some(3),
+                Self::AndroidWritable => Some(4),
+                Self::Read | Self::Write | Self::Legacy | Self::Full => None,
+                Self::Unknown(mode) => Some(mode),
+            }
+        } else {
+            match self {
+                Self::None => Some(0),
+                Self::Default => Some(1),
+                Self::Read => Some(2),
+                Self::Write => Some(3),
+                Self::Legacy => Some(4),
+                Self::Installer => Some(5),
+                Self::Full => Some(6),
+                Self::PassThrough => Some(7),
+                Self::AndroidWritable => Some(8),
+                Self::Unknown(mode) => Some(mode),
+            }
+        }
+    }
+
+    /// Returns whether the app gets any external storage.
+    #[inline]
+    pub fn has_storage(self) -> bool {
+        self != Self::None
+    }
+
+    /// Returns whether the app gets unrestricted access to all of external storage.
+    #[inline]
+    pub fn has_full_access(self) -> bool {
+        matches!(self, Self::PassThrough | Self::Full)
+    }
+
+    /// Returns whether `Android/data` is passed through to the lower file system rather than
+    /// served by FUSE.
+    #[inline]
+    pub fn is_android_data_pass_through(self) -> bool {
+        matches!(self, Self::PassThrough | Self::AndroidWritable)
+    }
+
+    /// Returns whether the app can write to `Android/obb`.
+    #[inline]
+    pub fn can_write_obb(self) -> bool {
+        matches!(
+            self,
+            Self::Installer | Self::Full | Self::PassThrough | Self::AndroidWritable
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MountExternal;
+
+    #[test]
+    fn round_trips_modes() {
+        for sdk in [30, 34] {
+            for mode in -1..10 {
+                assert_eq!(MountExternal::from_raw(mode, sdk).to_raw(sdk), Some(mode));
+            }
+        }
+        assert_eq!(MountExternal::from_raw(7, 34), MountExternal::Unknown(7));
+        assert_eq!(MountExternal::Full.to_raw(31), None);
+    }
+
+    #[test]
+    fn reads_android_11_modes() {
+        let write = MountExternal::from_raw(3, 30);
+        assert_eq!(write, MountExternal::Write);
+        assert!(write.has_storage() && !write.has_full_access() && !write.can_write_obb());
+
+        assert_eq!(MountExternal::from_raw(2, 30), MountExternal::Read);
+        assert_eq!(MountExternal::from_raw(7, 30), MountExternal::PassThrough);
+        assert_eq!(
+            MountExternal::from_raw(8, 30),
+            MountExternal::AndroidWritable
+        );
+        assert_eq!(MountExternal::AndroidWritable.to_raw(30), Some(8));
+    }
+
+    #[test]
+    fn describes_storage_view() {
+        let mode = MountExternal::from_raw(1, 34);
+        assert!(mode.has_storage() && !mode.is_android_data_pass_through());
+        assert!(!MountExternal::from_raw(0, 34).has_storage());
+        assert!(MountExternal::from_raw(2, 34).can_write_obb());
+        assert_eq!(MountExternal::AndroidWritable.to_raw(34), Some(4));
+    }
+}
@@ -0,0 +1,273 @@
+use std::{string::String, vec::Vec};
+
+use jni::{
+    JNIEnv,
+    objects::{JIntArray, JObjectArray, JString},
+    sys::{jint, jintArray, jobjectArray},
+};
+
+use super::{AnyAppSpecializeArgs, AnyServerSpecializeArgs};
+use crate::error::ZygiskError;
+
+/// A resource limit applied to the app process, as set by `setrlimit(2)`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Rlimit {
+    pub resource: i32,
+    pub soft: i32,
+    pub hard: i32,
+}
+
+/// A decoded snapshot of the arguments used to specialize an app process
+///
+/// Strings and arrays that are `null` on the Java side are decoded as [`None`], so they stay
+/// distinct from empty ones. Fields that do not exist in the API version the snapshot was taken
+/// from are [`None`] as well.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OwnedAppSpecializeArgs {
+    pub uid: i32,
+    pub gid: i32,
+    pub gids: Option<Vec<i32>>,
+    pub runtime_flags: i32,
+    pub rlimits: Option<Vec<Rlimit>>,
+    pub mount_external: i32,
+    pub se_info: Option<String>,
+    pub nice_name: Option<String>,
+    pub instruction_set: Option<String>,
+    pub app_data_dir: Option<String>,
+
+    pub fds_to_ignore: Option<Vec<i32>>,
+    pub is_child_zygote: Option<bool>,
+    pub is_top_app: Option<bool>,
+    pub pkg_data_info_list: Option<Vec<Option<String>>>,
+    pub whitelisted_data_info_list: Option<Vec<Option<String>>>,
+    pub mount_data_dirs: Option<bool>,
+    pub mount_storage_dirs: Option<bool>,
+    pub mount_sysprop_overrides: Option<bool>,
+}
+
+impl OwnedAppSpecializeArgs {
+    /// Decode the app specialization arguments of any API version.
+    pub fn new<'a>(
+        env: &mut JNIEnv,
+        args: &impl AnyAppSpecializeArgs<'a>,
+    ) -> Result<Self, ZygiskError> {
+        Ok(Self {
+            uid: args.uid(),
+            gid: args.gid(),
+            gids: decode_int_array(env, Some(args.gids()))?,
+            runtime_flags: args.runtime_flags(),
+            rlimits: decode_rlimits(env, args.rlimits())?,
+            mount_external: args.mount_external(),
+            se_info: decode_string(env, args.se_info())?,
+            nice_name: decode_string(env, args.nice_name())?,
+            instruction_set: decode_string(env, args.instruction_set())?,
+            app_data_dir: decode_string(env, args.app_data_dir())?,
+            fds_to_ignore: decode_int_array(env, args.fds_to_ignore())?,
+            is_child_zygote: args.is_child_zygote().map(|value| value != 0),
+            is_top_app: args.is_top_app().map(|value| value != 0),
+            pkg_data_info_list: decode_string_array(env, args.pkg_data_info_list())?,
+            whitelisted_data_info_list: decode_string_array(
+                env,
+                args.whitelisted_data_info_list(),
+            )?,
+            mount_data_dirs: args.mount_data_dirs().map(|value| value != 0),
+            mount_storage_dirs: args.mount_storage_dirs().map(|value| value != 0),
+            mount_sysprop_overrides: args.mount_sysprop_overrides().map(|value| value != 0),
+        })
+    }
+}
+
+/// A decoded snapshot of the arguments used to specialize the system server process
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OwnedServerSpecializeArgs {
+    pub uid: i32,
+    pub gid: i32,
+    pub gids: Option<Vec<i32>>,
+    pub runtime_flags: i32,
+    pub permitted_capabilities: i64,
+    pub effective_capabilities: i64,
+}
+
+impl OwnedServerSpecializeArgs {
+    /// Decode the system server specialization arguments (identical across API versions).
+    pub fn new<'a>(
+        env: &mut JNIEnv,
+        args: &impl AnyServerSpecializeArgs<'a>,
+    ) -> Result<Self, ZygiskError> {
+        Ok(Self {
+            uid: args.uid(),
+            gid: args.gid(),
+            gids: decode_int_array(env, Some(args.gids()))?,
+            runtime_flags: args.runtime_flags(),
+            permitted_capabilities: args.permitted_capabilities(),
+            effective_capabilities: args.effective_capabilities(),
+        })
+    }
+}
+
+pub(crate) fn decode_string(
+    env: &mut JNIEnv,
+    string: &JString,
+) -> Result<Option<String>, ZygiskError> {
+    if string.is_null() {
+        return Ok(None);
+    }
+
+    // SAFETY: the arguments of zygote's specialization methods are `java.lang.String`s.
+    Ok(Some(unsafe { env.get_string_unchecked(string) }?.into()))
+}
+
+fn decode_int_array(
+    env: &mut JNIEnv,
+    array: Option<jintArray>,
+) -> Result<Option<Vec<i32>>, ZygiskError> {
+    let Some(array) = array.filter(|array| !array.is_null()) else {
+        return Ok(None);
+    };
+
+    // SAFETY: the array is borrowed from zygote's arguments, and is not deleted when dropped.
+    let array = unsafe { JIntArray::from_raw(array) };
+    let mut elements = Vec::new();
+    elements.resize(env.get_array_length(&array)? as usize, 0 as jint);
+    env.get_int_array_region(&array, 0, &mut elements)?;
+
+    Ok(Some(elements))
+}
+
+fn decode_string_array(
+    env: &mut JNIEnv,
+    array: Option<jobjectArray>,
+) -> Result<Option<Vec<Option<String>>>, ZygiskError> {
+    let Some(array) = array.filter(|array| !array.is_null()) else {
+        return Ok(None);
+    };
+
+    // SAFETY: the array is borrowed from zygote's arguments, and is not deleted when dropped.
+    let array = unsafe { JObjectArray::from_raw(array) };
+    let len = env.get_array_length(&array)?;
+
+    let mut strings = Vec::with_capacity(len as usize);
+    for index in 0..len {
+        let string = JString::from(env.get_object_array_element(&array, index)?);
+        strings.push(decode_string(env, &string)?);
+        env.delete_local_ref(string)?;
+    }
+
+    Ok(Some(strings))
+}
+
+fn decode_rlimits(
+    env: &mut JNIEnv,
+    array: Option<jobjectArray>,
+) -> Result<Option<Vec<Rlimit>>, ZygiskError> {
+    let Some(array) = array.filter(|array| !array.is_null()) else {
+        return Ok(None);
+    };
+
+    // SAFETY: the array is borrowed from zygote's arguments, and is not deleted when dropped.
+    let array = unsafe { JObjectArray::from_raw(array) };
+    let len = env.get_array_length(&array)?;
+
+    let mut rlimits = Vec::with_capacity(len as usize);
+    for index in 0..len {
+        let rlimit = JIntArray::from(env.get_object_array_element(&array, index)?);
+        let mut values = [0; 3];
+        env.get_int_array_region(&rlimit, 0, &mut values)?;
+        env.delete_local_ref(rlimit)?;
+
+        let [resource, soft, hard] = values;
+        rlimits.push(Rlimit {
+            resource,
+            soft,
+            hard,
+        });
+    }
+
+    Ok(Some(rlimits))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{string::String, vec, vec::Vec};
+
+    use super::{OwnedAppSpecializeArgs, OwnedServerSpecializeArgs, Rlimit};
+    use crate::testing::{FakeAppArgs, FakeJvm, FakeServerArgs};
+
+    #[test]
+    fn decodes_app_args() {
+        let jvm = FakeJvm::new();
+        let mut fake = FakeAppArgs::new(&jvm, 10123, "com.example");
+        fake.gids = jvm.new_int_array(&[3003, 9997]);
+        fake.runtime_flags = 0x4;
+        fake.rlimits = jvm.new_object_array(&[jvm.new_int_array(&[7, 1024, 4096])]);
+        fake.mount_external = 1;
+        fake.se_info = jvm.new_string("default:targetSdkVersion=34");
+        fake.fds_to_ignore = Some(jvm.new_int_array(&[42]));
+        fake.is_top_app = Some(1);
+        fake.pkg_data_info_list =
+            Some(jvm.new_object_array(&[jvm.new_string("com.example"), jvm.new_string("10123")]));
+        fake.mount_data_dirs = Some(1);
+
+        assert_eq!(
+            OwnedAppSpecializeArgs::new(&mut jvm.env(), &fake.v5()).unwrap(),
+            OwnedAppSpecializeArgs {
+                uid: 10123,
+                gid: 10123,
+                gids: Some(vec![3003, 9997]),
+                runtime_flags: 0x4,
+                rlimits: Some(vec![Rlimit {
+                    resource: 7,
+                    soft: 1024,
+                    hard: 4096
+                }]),
+                mount_external: 1,
+                se_info: Some("default:targetSdkVersion=34".into()),
+                nice_name: Some("com.example".into()),
+                instruction_set: Some("arm64".into()),
+                app_data_dir: None,
+                fds_to_ignore: Some(vec![42]),
+                is_child_zygote: None,
+                is_top_app: Some(true),
+                pkg_data_info_list: Some(vec![Some("com.example".into()), Some("10123".into())]),
+                whitelisted_data_info_list: None,
+                mount_data_dirs: Some(true),
+                mount_storage_dirs: None,
+                mount_sysprop_overrides: None,
+            }
+        );
+    }
+
+    #[test]
+    fn keeps_null_distinct_from_empty() {
+        let jvm = FakeJvm::new();
+        let mut fake = FakeAppArgs::new(&jvm, 10123, "com.example");
+        fake.gids = jvm.new_int_array(&[]);
+        fake.se_info = jvm.new_string("");
+        fake.fds_to_ignore = Some(core::ptr::null_mut());
+        fake.pkg_data_info_list =
+            Some(jvm.new_object_array(&[core::ptr::null_mut(), jvm.new_string("")]));
+
+        let owned = OwnedAppSpecializeArgs::new(&mut jvm.env(), &fake.v5()).unwrap();
+        assert_eq!(owned.gids, Some(Vec::new()));
+        assert_eq!(owned.se_info, Some(String::new()));
+        assert_eq!(owned.app_data_dir, None);
+        assert_eq!(owned.fds_to_ignore, None);
+        assert_eq!(
+            owned.pkg_data_info_list,
+            Some(vec![None, Some(String::new())])
+        );
+    }
+
+    #[test]
+    fn decodes_server_args() {
+        let jvm = FakeJvm::new();
+        let mut fake = FakeServerArgs::new(&jvm);
+        fake.gids = jvm.new_int_array(&[1001, 1002]);
+        fake.permitted_capabilities = 0x3f;
+        fake.effective_capabilities = 0x3f;
+
+        let owned = OwnedServerSpecializeArgs::new(&mut jvm.env(), &fake.args()).unwrap();
+        assert_eq!(owned.gids, Some(Vec::from([1001, 1002])));
+        assert_eq!(owned.permitted_capabilities, 0x3f);
+    }
+}
@@ -0,0 +1,204 @@
+use jni::sys::jint;
+
+bitflags::bitflags! {
+    /// The `runtime_flags` specialization argument, as defined in `com.android.internal.os.Zygote`
+    ///
+    /// Besides single-bit flags, `runtime_flags` packs a few multi-bit fields. Their masks are
+    /// available as `*_MASK` constants, and their values through typed accessors such as
+    /// [`memory_tag_level`](RuntimeFlags::memory_tag_level). Bits unknown to this crate are
+    /// retained by the conversions from and to [`jint`].
+    ///
+    /// ```
+    /// use zygisk_api::args::{MemoryTagLevel, RuntimeFlags};
+    ///
+    /// let mut flags = RuntimeFlags::from(0x0010_0000);
+    /// assert_eq!(flags.memory_tag_level(), MemoryTagLevel::Async);
+    ///
+    /// flags.set_debuggable(true);
+    /// assert!(flags.is_debuggable());
+    /// assert_eq!(jni::sys::jint::from(flags), 0x0010_0101);
+    /// ```
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct RuntimeFlags: u32 {
+        /// Enable the JDWP debugger agent
+        const DEBUG_ENABLE_JDWP = (1 << 0);
+        const DEBUG_ENABLE_CHECKJNI = (1 << 1);
+        const DEBUG_ENABLE_ASSERT = (1 << 2);
+        const DEBUG_ENABLE_SAFEMODE = (1 << 3);
+        const DEBUG_ENABLE_JNI_LOGGING = (1 << 4);
+        const DEBUG_GENERATE_DEBUG_INFO = (1 << 5);
+        const DEBUG_ALWAYS_JIT = (1 << 6);
+        const DEBUG_NATIVE_DEBUGGABLE = (1 << 7);
+        /// The app is debuggable, as if `android:debuggable` was set in its manifest
+        const DEBUG_JAVA_DEBUGGABLE = (1 << 8);
+        const DISABLE_VERIFIER = (1 << 9);
+        const ONLY_USE_SYSTEM_OAT_FILES = (1 << 10);
+        const DEBUG_GENERATE_MINI_DEBUG_INFO = (1 << 11);
+        /// See [`HiddenApiEnforcementPolicy`]
+        const HIDDEN_API_ENFORCEMENT_POLICY_MASK = (0b11 << 12);
+        const PROFILE_SYSTEM_SERVER = (1 << 14);
+        const PROFILE_FROM_SHELL = (1 << 15);
+        const USE_APP_IMAGE_STARTUP_CACHE = (1 << 16);
+        const DEBUG_IGNORE_APP_SIGNAL_HANDLER = (1 << 17);
+        const DISABLE_TEST_API_ENFORCEMENT_POLICY = (1 << 18);
+        /// See [`MemoryTagLevel`]
+        const MEMORY_TAG_LEVEL_MASK = (0b11 << 19);
+        /// See [`GwpAsanLevel`]
+        const GWP_ASAN_LEVEL_MASK = (0b11 << 21);
+        const NATIVE_HEAP_ZERO_INIT_ENABLED = (1 << 23);
+        const PROFILEABLE = (1 << 24);
+        const DEBUG_ENABLE_PTRACE = (1 << 25);
+    }
+}
+
+/// Enforcement of restrictions on non-SDK interfaces
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum HiddenApiEnforcementPolicy {
+    Disabled = 0,
+    JustWarn = 1,
+    Enabled = 2,
+}
+
+/// Memory tagging (MTE) mode of the heap allocator
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum MemoryTagLevel {
+    None = 0,
+    /// Top-byte ignore: pointer tags are allowed, but not checked
+    Tbi = 1,
+    Async = 2,
+    Sync = 3,
+}
+
+/// Sampling mode of the GWP-ASan allocator
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum GwpAsanLevel {
+    Never = 0,
+    Lottery = 1,
+    Always = 2,
+    /// Let the platform decide (Android 14+)
+    Default = 3,
+}
+
+impl RuntimeFlags {
+    /// Returns whether the app is debuggable.
+    #[inline]
+    pub fn is_debuggable(self) -> bool {
+        self.contains(Self::DEBUG_JAVA_DEBUGGABLE)
+    }
+
+    /// Makes the app debuggable, and allows a debugger to attach to it, or reverts both.
+    #[inline]
+    pub fn set_debuggable(&mut self, debuggable: bool) {
+        self.set(
+            Self::DEBUG_JAVA_DEBUGGABLE | Self::DEBUG_ENABLE_JDWP,
+            debuggable,
+        );
+    }
+
+    /// Returns the hidden API enforcement policy, or [`None`] if the field holds an undefined value.
+    #[inline]
+    pub fn hidden_api_enforcement_policy(self) -> Option<HiddenApiEnforcementPolicy> {
+        match self.field(Self::HIDDEN_API_ENFORCEMENT_POLICY_MASK) {
+            0 => Some(HiddenApiEnforcementPolicy::Disabled),
+            1 => Some(HiddenApiEnforcementPolicy::JustWarn),
+            2 => Some(HiddenApiEnforcementPolicy::Enabled),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn set_hidden_api_enforcement_policy(&mut self, policy: HiddenApiEnforcementPolicy) {
+        self.set_field(Self::HIDDEN_API_ENFORCEMENT_POLICY_MASK, policy as u32);
+    }
+
+    #[inline]
+    pub fn memory_tag_level(self) -> MemoryTagLevel {
+        match self.field(Self::MEMORY_TAG_LEVEL_MASK) {
+            0 => MemoryTagLevel::None,
+            1 => MemoryTagLevel::Tbi,
+            2 => MemoryTagLevel::Async,
+            _ => MemoryTagLevel::Sync,
+        }
+    }
+
+    #[inline]
+    pub fn set_memory_tag_level(&mut self, level: MemoryTagLevel) {
+        self.set_field(Self::MEMORY_TAG_LEVEL_MASK, level as u32);
+    }
+
+    #[inline]
+    pub fn gwp_asan_level(self) -> GwpAsanLevel {
+        match self.field(Self::GWP_ASAN_LEVEL_MASK) {
+            0 => GwpAsanLevel::Never,
+            1 => GwpAsanLevel::Lottery,
+            2 => GwpAsanLevel::Always,
+            _ => GwpAsanLevel::Default,
+        }
+    }
+
+    #[inline]
+    pub fn set_gwp_asan_level(&mut self, level: GwpAsanLevel) {
+        self.set_field(Self::GWP_ASAN_LEVEL_MASK, level as u32);
+    }
+
+    #[inline]
+    fn field(self, mask: Self) -> u32 {
+        (self.bits() & mask.bits()) >> mask.bits().trailing_zeros()
+    }
+
+    #[inline]
+    fn set_field(&mut self, mask: Self, value: u32) {
+        let value = (value << mask.bits().trailing_zeros()) & mask.bits();
+        *self = Self::from_bits_retain((self.bits() & !mask.bits()) | value);
+    }
+}
+
+impl From<jint> for RuntimeFlags {
+    #[inline]
+    fn from(flags: jint) -> Self {
+        Self::from_bits_retain(flags as u32)
+    }
+}
+
+impl From<RuntimeFlags> for jint {
+    #[inline]
+    fn from(flags: RuntimeFlags) -> Self {
+        flags.bits() as jint
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jni::sys::jint;
+
+    use super::{GwpAsanLevel, HiddenApiEnforcementPolicy, MemoryTagLevel, RuntimeFlags};
+
+    #[test]
+    fn edits_fields() {
+        // JDWP, hidden API enforcement enabled, sync MTE, GWP-ASan lottery and an unknown bit
+        let raw: jint = 0x1 | (2 << 12) | (3 << 19) | (1 << 21) | (1 << 30);
+        let mut flags = RuntimeFlags::from(raw);
+
+        assert!(!flags.is_debuggable());
+        assert_eq!(
+            flags.hidden_api_enforcement_policy(),
+            Some(HiddenApiEnforcementPolicy::Enabled)
+        );
+        assert_eq!(flags.memory_tag_level(), MemoryTagLevel::Sync);
+        assert_eq!(flags.gwp_asan_level(), GwpAsanLevel::Lottery);
+
+        flags.set_debuggable(true);
+        flags.set_hidden_api_enforcement_policy(HiddenApiEnforcementPolicy::Disabled);
+        flags.set_memory_tag_level(MemoryTagLevel::Tbi);
+        flags.set_gwp_asan_level(GwpAsanLevel::Never);
+
+        assert!(flags.is_debuggable());
+        assert_eq!(jint::from(flags), 0x101 | (1 << 19) | (1 << 30));
+
+        flags.set_debuggable(false);
+        assert_eq!(jint::from(flags), (1 << 19) | (1 << 30));
+    }
+}
@@ -0,0 +1,319 @@
+use core::{mem, ptr};
+use std::{
+    io::{self, Read, Write},
+    os::{
+        fd::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd},
+        unix::net::UnixStream,
+    },
+    vec,
+    vec::Vec,
+};
+
+use libc::c_int;
+
+use super::MAX_FRAME_LEN;
+use crate::{error::ZygiskError, impl_sealing::Sealed};
+
+/// Largest number of file descriptors the kernel passes in a single message (`SCM_MAX_FD`)
+pub const MAX_FDS: usize = 253;
+
+/// Payload length as a `u32`, followed by the number of file descriptors as a `u8`
+const HEADER_LEN: usize = 5;
+
+const CMSG_BUF_LEN: usize =
+    unsafe { libc::CMSG_SPACE((MAX_FDS * size_of::<c_int>()) as u32) } as usize;
+
+#[repr(C, align(8))]
+struct CmsgBuf([u8; CMSG_BUF_LEN]);
+
+impl Sealed for UnixStream {}
+
+/// Passing file descriptors over the companion socket (`SCM_RIGHTS`)
+///
+/// This lets the root companion open files that the specialized process cannot reach, and hand
+/// them back to the module:
+///
+/// ```no_run
+/// use std::{fs::File, os::{fd::AsFd, unix::net::UnixStream}};
+///
+/// use zygisk_api::companion::FdPassing;
+///
+/// fn companion(stream: &mut UnixStream) {
+///     let Ok((_, path)) = stream.recv_fds() else { return };
+///     let Ok(file) = File::open(String::from_utf8_lossy(&path).as_ref()) else { return };
+///     let _ = stream.send_fds(&[file.as_fd()], &[]);
+/// }
+/// ```
+pub trait FdPassing: Sealed {
+    /// Sends `fds` along with an optional `payload`, which may be empty.
+    ///
+    /// Returns [`ZygiskError::TooManyFds`] if more than [`MAX_FDS`] file descriptors are given.
+    fn send_fds(&mut self, fds: &[BorrowedFd<'_>], payload: &[u8]) -> Result<(), ZygiskError>;
+
+    /// Receives the file descriptors and payload of a message sent with
+    /// [`send_fds`](FdPassing::send_fds). The file descriptors are close-on-exec.
+    ///
+    /// Returns [`ZygiskError::FdsTruncated`] if the kernel could not pass every file descriptor,
+    /// such as when the process ran out of them.
+    fn recv_fds(&mut self) -> Result<(Vec<OwnedFd>, Vec<u8>), ZygiskError>;
+}
+
+impl FdPassing for UnixStream {
+    fn send_fds(&mut self, fds: &[BorrowedFd<'_>], payload: &[u8]) -> Result<(), ZygiskError> {
+        if fds.len() > MAX_FDS {
+            return Err(ZygiskError::TooManyFds(fds.len()));
+        }
+        if payload.len() > MAX_FRAME_LEN {
+            return Err(ZygiskError::CompanionFrameTooLarge(payload.len()));
+        }
+
+        let mut header = [0; HEADER_LEN];
+        header[..4].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+        header[4] = fds.len() as u8;
+
+        let sent = send_with_fds(self, fds, &[&header, payload])?;
+
+        // The file descriptors went along with the first byte, so the rest is plain data
+        let written = if sent < HEADER_LEN {
+            self.write_all(&header[sent..])
+                .and_then(|()| self.write_all(payload))
+        } else {
+            self.write_all(&payload[sent - HEADER_LEN..])
+        };
+        written.map_err(|err| ZygiskError::CompanionIoError(err.kind()))
+    }
+
+    fn recv_fds(&mut self) -> Result<(Vec<OwnedFd>, Vec<u8>), ZygiskError> {
+        let mut header = [0; HEADER_LEN];
+        let mut iov = [libc::iovec {
+            iov_base: header.as_mut_ptr().cast(),
+            iov_len: header.len(),
+        }];
+        let mut cmsg = CmsgBuf([0; CMSG_BUF_LEN]);
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_iov = iov.as_mut_ptr();
+        msg.msg_iovlen = iov.len() as _;
+        msg.msg_control = cmsg.0.as_mut_ptr().cast();
+        msg.msg_controllen = CMSG_BUF_LEN as _;
+
+        let received = loop {
+            match unsafe { libc::recvmsg(self.as_raw_fd(), &mut msg, libc::MSG_CMSG_CLOEXEC) } {
+                -1 => match io::Error::last_os_error() {
+                    err if err.kind() == io::ErrorKind::Interrupted => continue,
+                    err => return Err(ZygiskError::CompanionIoError(err.kind())),
+                },
+                received => break received as usize,
+            }
+        };
+
+        // Take ownership first, so that the file descriptors are closed on every error path
+        let mut fds = Vec::new();
+        unsafe {
+            let mut hdr = libc::CMSG_FIRSTHDR(&msg);
+            while !hdr.is_null() {
+                if (*hdr).cmsg_level == libc::SOL_SOCKET && (*hdr).cmsg_type == libc::SCM_RIGHTS {
+                    let data_len = (*hdr).cmsg_len as usize - libc::CMSG_LEN(0) as usize;
+                    let data = libc::CMSG_DATA(hdr).cast::<c_int>();
+                    for i in 0..data_len / size_of::<c_int>() {
+                        fds.push(OwnedFd::from_raw_fd(ptr::read_unaligned(data.add(i))));
+                    }
+                }
+                hdr = libc::CMSG_NXTHDR(&msg, hdr);
+            }
+        }
+
+        if msg.msg_flags & libc::MSG_CTRUNC != 0 {
+            return Err(ZygiskError::FdsTruncated);
+        }
+        if received == 0 {
+            return Err(ZygiskError::CompanionIoError(io::ErrorKind::UnexpectedEof));
+        }
+        self.read_exact(&mut header[received..])
+            .map_err(|err| ZygiskError::CompanionIoError(err.kind()))?;
+
+        if fds.len() != header[4] as usize {
+            return Err(ZygiskError::CompanionMessageError);
+        }
+        let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        if len > MAX_FRAME_LEN {
+            return Err(ZygiskError::CompanionFrameTooLarge(len));
+        }
+        let mut payload = vec![0; len];
+        self.read_exact(&mut payload)
+            .map_err(|err| ZygiskError::CompanionIoError(err.kind()))?;
+
+        Ok((fds, payload))
+    }
+}
+
+/// Sends `bufs` in a single `sendmsg(2)` call, with `fds` attached to the first byte. Returns the
+/// number of bytes sent.
+fn send_with_fds(
+    stream: &UnixStream,
+    fds: &[BorrowedFd<'_>],
+    bufs: &[&[u8]],
+) -> Result<usize, ZygiskError> {
+    let mut iov: Vec<libc::iovec> = bufs
+        .iter()
+        .map(|buf| libc::iovec {
+            iov_base: buf.as_ptr().cast_mut().cast(),
+            iov_len: buf.len(),
+        })
+        .collect();
+    let mut cmsg = CmsgBuf([0; CMSG_BUF_LEN]);
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = iov.as_mut_ptr();
+    msg.msg_iovlen = iov.len() as _;
+
+    if !fds.is_empty() {
+        let data_len = (fds.len() * size_of::<c_int>()) as u32;
+        msg.msg_control = cmsg.0.as_mut_ptr().cast();
+        msg.msg_controllen = unsafe { libc::CMSG_SPACE(data_len) } as _;
+        unsafe {
+            let hdr = libc::CMSG_FIRSTHDR(&msg);
+            (*hdr).cmsg_level = libc::SOL_SOCKET;
+            (*hdr).cmsg_type = libc::SCM_RIGHTS;
+            (*hdr).cmsg_len = libc::CMSG_LEN(data_len) as _;
+            let data = libc::CMSG_DATA(hdr).cast::<c_int>();
+            for (i, fd) in fds.iter().enumerate() {
+                ptr::write_unaligned(data.add(i), fd.as_raw_fd());
+            }
+        }
+    }
+
+    loop {
+        match unsafe { libc::sendmsg(stream.as_raw_fd(), &msg, libc::MSG_NOSIGNAL) } {
+            -1 => match io::Error::last_os_error() {
+                err if err.kind() == io::ErrorKind::Interrupted => continue,
+                err => return Err(ZygiskError::CompanionIoError(err.kind())),
+            },
+            sent => return Ok(sent as usize),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::{
+        io::{self, Read, Write},
+        os::{
+            fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd},
+            unix::net::UnixStream,
+        },
+        vec::Vec,
+    };
+
+    use super::{FdPassing, MAX_FDS, send_with_fds};
+    use crate::error::ZygiskError;
+
+    /// Returns a file descriptor to pass, and a stream that reaches its end once every copy of
+    /// that file descriptor has been closed.
+    fn watched_fd() -> (OwnedFd, UnixStream) {
+        let (watched, peer) = UnixStream::pair().unwrap();
+        peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        (watched.into(), peer)
+    }
+
+    fn is_closed(peer: &mut UnixStream) -> bool {
+        matches!(peer.read(&mut [0]), Ok(0))
+    }
+
+    #[test]
+    fn passes_fds() {
+        let (mut module, mut companion) = UnixStream::pair().unwrap();
+        let (mut read, write) = UnixStream::pair().unwrap();
+
+        companion
+            .send_fds(&[write.as_fd(), write.as_fd()], b"config")
+            .unwrap();
+        companion.send_fds(&[], &[]).unwrap();
+        drop(write);
+
+        let (fds, payload) = module.recv_fds().unwrap();
+        assert_eq!(fds.len(), 2);
+        assert_eq!(payload, b"config");
+        UnixStream::from(fds.into_iter().next().unwrap())
+            .write_all(b"ok")
+            .unwrap();
+        let mut buf = [0; 2];
+        read.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ok");
+
+        let (fds, payload) = module.recv_fds().unwrap();
+        assert!(fds.is_empty() && payload.is_empty());
+
+        drop(companion);
+        assert!(matches!(
+            module.recv_fds(),
+            Err(ZygiskError::CompanionIoError(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_too_many_fds() {
+        let (module, _companion) = UnixStream::pair().unwrap();
+        let fds: Vec<BorrowedFd<'_>> = (0..=MAX_FDS).map(|_| module.as_fd()).collect();
+        assert!(matches!(
+            module.try_clone().unwrap().send_fds(&fds, &[]),
+            Err(ZygiskError::TooManyFds(254))
+        ));
+    }
+
+    #[test]
+    fn closes_fds_on_short_header() {
+        let (mut module, companion) = UnixStream::pair().unwrap();
+        let (fd, mut peer) = watched_fd();
+
+        send_with_fds(&companion, &[fd.as_fd()], &[&[6, 0]]).unwrap();
+        drop((fd, companion));
+
+        assert!(matches!(
+            module.recv_fds(),
+            Err(ZygiskError::CompanionIoError(io::ErrorKind::UnexpectedEof))
+        ));
+        assert!(is_closed(&mut peer));
+    }
+
+    #[test]
+    fn closes_fds_on_count_mismatch() {
+        let (mut module, companion) = UnixStream::pair().unwrap();
+        let (fd, mut peer) = watched_fd();
+
+        send_with_fds(&companion, &[fd.as_fd()], &[&[0, 0, 0, 0, 2]]).unwrap();
+        drop(fd);
+
+        assert!(matches!(
+            module.recv_fds(),
+            Err(ZygiskError::CompanionMessageError)
+        ));
+        assert!(is_closed(&mut peer));
+    }
+
+    #[test]
+    fn reports_truncated_fds() {
+        let (mut module, mut companion) = UnixStream::pair().unwrap();
+        let (fd, mut peer) = watched_fd();
+
+        // Credentials take up part of the control buffer, leaving no room for MAX_FDS descriptors
+        let enable: libc::c_int = 1;
+        let ret = unsafe {
+            libc::setsockopt(
+                module.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PASSCRED,
+                (&enable as *const libc::c_int).cast(),
+                size_of::<libc::c_int>() as _,
+            )
+        };
+        assert_eq!(ret, 0);
+
+        let fds: Vec<BorrowedFd<'_>> = (0..MAX_FDS).map(|_| fd.as_fd()).collect();
+        companion.send_fds(&fds, &[]).unwrap();
+        drop(fds);
+        drop(fd);
+
+        assert!(matches!(module.recv_fds(), Err(ZygiskError::FdsTruncated)));
+        assert!(is_closed(&mut peer));
+    }
+}
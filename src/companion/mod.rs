@@ -0,0 +1,52 @@
+//! Structured communication between the module and its root companion process.
+//!
+//! [`ZygiskApi::with_companion`](crate::api::ZygiskApi#method.with_companion) and
+//! [`register_companion!`](crate::register_companion) hand both sides a bare [`UnixStream`]. The
+//! [`Protocol`] of a module describes the requests it sends and the responses it expects, which
+//! [`RpcClient`] and [`serve`] exchange as length-prefixed frames after a version handshake:
+//!
+//! ```
+//! use std::{os::unix::net::UnixStream, string::String, thread};
+//!
+//! use zygisk_api::{
+//!     companion::{Decoder, Encoder, Message, Protocol, RpcClient, RpcHandler, serve},
+//!     error::ZygiskError,
+//! };
+//!
+//! struct ConfigProtocol;
+//!
+//! impl Protocol for ConfigProtocol {
+//!     const VERSION: u32 = 1;
+//!     type Request = String;
+//!     type Response = Option<Vec<u8>>;
+//! }
+//!
+//! struct Configs;
+//!
+//! impl RpcHandler for Configs {
+//!     type Protocol = ConfigProtocol;
+//!
+//!     fn handle(&mut self, package: String) -> Option<Vec<u8>> {
+//!         (package == "com.example.app").then(|| b"enabled".to_vec())
+//!     }
+//! }
+//!
+//! let (mut module, mut companion) = UnixStream::pair().unwrap();
+//! let server = thread::spawn(move || serve(&mut companion, &mut Configs));
+//!
+//! let mut client = RpcClient::<ConfigProtocol>::connect(&mut module).unwrap();
+//! assert_eq!(client.call(&"com.example.app".into()).unwrap(), Some(b"enabled".to_vec()));
+//! drop(client);
+//! drop(module);
+//! server.join().unwrap().unwrap();
+//! ```
+//!
+//! File descriptors that the companion opens on behalf of the module are passed with
+//! [`FdPassing`].
+//!
+//! [`UnixStream`]: std::os::unix::net::UnixStream
+
+mod fds;
+pub use fds::*;
+mod rpc;
+pub use rpc::*;
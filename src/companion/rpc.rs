@@ -0,0 +1,565 @@
+use std::{
+    io::{self, Read, Write},
+    string::String,
+    vec::Vec,
+};
+
+use crate::error::ZygiskError;
+
+/// Largest payload accepted in a single frame
+pub const MAX_FRAME_LEN: usize = 1 << 20;
+
+/// Sent by the client at the start of the handshake
+const MAGIC: [u8; 4] = *b"ZRPC";
+
+/// The messages exchanged between a module and its companion
+///
+/// Both sides must agree on [`Protocol::VERSION`]; bump it whenever the encoding of the requests or
+/// responses changes.
+pub trait Protocol {
+    const VERSION: u32;
+    type Request: Message;
+    type Response: Message;
+}
+
+/// A value with a deterministic binary encoding
+///
+/// Integers are encoded in little-endian, and variable-length values are prefixed with their
+/// length as a `u32`. Decoding fails unless the whole payload of the frame is consumed.
+pub trait Message: Sized {
+    fn encode(&self, encoder: &mut Encoder<'_>);
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, ZygiskError>;
+}
+
+/// Appends encoded values to a frame payload
+pub struct Encoder<'a> {
+    buf: &'a mut Vec<u8>,
+}
+
+impl<'a> Encoder<'a> {
+    pub fn new(buf: &'a mut Vec<u8>) -> Self {
+        Self { buf }
+    }
+
+    #[inline]
+    pub fn put_u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    #[inline]
+    pub fn put_bool(&mut self, value: bool) {
+        self.put_u8(value as u8);
+    }
+
+    #[inline]
+    pub fn put_u32(&mut self, value: u32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn put_i32(&mut self, value: i32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn put_u64(&mut self, value: u64) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn put_i64(&mut self, value: i64) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn put_bytes(&mut self, bytes: &[u8]) {
+        self.put_u32(bytes.len() as u32);
+        self.buf.extend_from_slice(bytes);
+    }
+
+    #[inline]
+    pub fn put_str(&mut self, string: &str) {
+        self.put_bytes(string.as_bytes());
+    }
+
+    #[inline]
+    pub fn put<M: Message>(&mut self, message: &M) {
+        message.encode(self);
+    }
+}
+
+/// Reads encoded values from a frame payload, without copying
+pub struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    /// Returns whether the whole payload has been consumed.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], ZygiskError> {
+        let (head, rest) = self
+            .bytes
+            .split_first_chunk()
+            .ok_or(ZygiskError::CompanionMessageError)?;
+        self.bytes = rest;
+        Ok(*head)
+    }
+
+    #[inline]
+    pub fn get_u8(&mut self) -> Result<u8, ZygiskError> {
+        self.take::<1>().map(|[value]| value)
+    }
+
+    /// Fails on values other than `0` and `1`.
+    #[inline]
+    pub fn get_bool(&mut self) -> Result<bool, ZygiskError> {
+        match self.get_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(ZygiskError::CompanionMessageError),
+        }
+    }
+
+    #[inline]
+    pub fn get_u32(&mut self) -> Result<u32, ZygiskError> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    #[inline]
+    pub fn get_i32(&mut self) -> Result<i32, ZygiskError> {
+        self.take().map(i32::from_le_bytes)
+    }
+
+    #[inline]
+    pub fn get_u64(&mut self) -> Result<u64, ZygiskError> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    #[inline]
+    pub fn get_i64(&mut self) -> Result<i64, ZygiskError> {
+        self.take().map(i64::from_le_bytes)
+    }
+
+    #[inline]
+    pub fn get_bytes(&mut self) -> Result<&'a [u8], ZygiskError> {
+        let len = self.get_u32()? as usize;
+        if len > self.bytes.len() {
+            return Err(ZygiskError::CompanionMessageError);
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    /// Fails on invalid UTF-8.
+    #[inline]
+    pub fn get_str(&mut self) -> Result<&'a str, ZygiskError> {
+        str::from_utf8(self.get_bytes()?).map_err(|_| ZygiskError::CompanionMessageError)
+    }
+
+    #[inline]
+    pub fn get<M: Message>(&mut self) -> Result<M, ZygiskError> {
+        M::decode(self)
+    }
+}
+
+macro_rules! impl_message {
+    ($($ty:ty => $put:ident, $get:ident;)+) => {
+        $(
+            impl Message for $ty {
+                #[inline]
+                fn encode(&self, encoder: &mut Encoder<'_>) {
+                    encoder.$put(*self);
+                }
+
+                #[inline]
+                fn decode(decoder: &mut Decoder<'_>) -> Result<Self, ZygiskError> {
+                    decoder.$get()
+                }
+            }
+        )+
+    };
+}
+
+impl_message! {
+    bool => put_bool, get_bool;
+    u8 => put_u8, get_u8;
+    u32 => put_u32, get_u32;
+    i32 => put_i32, get_i32;
+    u64 => put_u64, get_u64;
+    i64 => put_i64, get_i64;
+}
+
+impl Message for () {
+    #[inline]
+    fn encode(&self, _: &mut Encoder<'_>) {}
+
+    #[inline]
+    fn decode(_: &mut Decoder<'_>) -> Result<Self, ZygiskError> {
+        Ok(())
+    }
+}
+
+impl Message for String {
+    #[inline]
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.put_str(self);
+    }
+
+    #[inline]
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, ZygiskError> {
+        decoder.get_str().map(String::from)
+    }
+}
+
+impl Message for Vec<u8> {
+    #[inline]
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.put_bytes(self);
+    }
+
+    #[inline]
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, ZygiskError> {
+        decoder.get_bytes().map(<[u8]>::to_vec)
+    }
+}
+
+impl<M: Message> Message for Option<M> {
+    #[inline]
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.put_bool(self.is_some());
+        if let Some(message) = self {
+            message.encode(encoder);
+        }
+    }
+
+    #[inline]
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, ZygiskError> {
+        match decoder.get_bool()? {
+            true => M::decode(decoder).map(Some),
+            false => Ok(None),
+        }
+    }
+}
+
+/// The module side of a [`Protocol`]
+///
+/// The buffer holding frames is reused across calls.
+pub struct RpcClient<'s, P: Protocol> {
+    stream: &'s mut (dyn ReadWrite + 's),
+    buf: Vec<u8>,
+    _protocol: core::marker::PhantomData<P>,
+}
+
+/// A bidirectional byte stream, such as a [`UnixStream`](std::os::unix::net::UnixStream)
+pub trait ReadWrite: Read + Write {}
+
+impl<T: Read + Write> ReadWrite for T {}
+
+impl<'s, P: Protocol> RpcClient<'s, P> {
+    /// Performs the version handshake with the companion.
+    ///
+    /// Returns [`ZygiskError::CompanionVersionMismatch`] if the companion speaks another version of
+    /// the protocol.
+    pub fn connect(stream: &'s mut (impl ReadWrite + 's)) -> Result<Self, ZygiskError> {
+        let mut client = Self {
+            stream,
+            buf: Vec::new(),
+            _protocol: core::marker::PhantomData,
+        };
+
+        client.buf.extend_from_slice(&MAGIC);
+        client.buf.extend_from_slice(&P::VERSION.to_le_bytes());
+        write_frame(client.stream, &client.buf)?;
+
+        if !read_frame(client.stream, &mut client.buf)? {
+            return Err(ZygiskError::CompanionIoError(io::ErrorKind::UnexpectedEof));
+        }
+        let remote = decode_whole::<u32>(&client.buf)?;
+        if remote != P::VERSION {
+            return Err(ZygiskError::CompanionVersionMismatch {
+                local: P::VERSION,
+                remote,
+            });
+        }
+
+        Ok(client)
+    }
+
+    /// Sends `request` and waits for the response.
+    pub fn call(&mut self, request: &P::Request) -> Result<P::Response, ZygiskError> {
+        self.buf.clear();
+        request.encode(&mut Encoder::new(&mut self.buf));
+        write_frame(self.stream, &self.buf)?;
+
+        if !read_frame(self.stream, &mut self.buf)? {
+            return Err(ZygiskError::CompanionIoError(io::ErrorKind::UnexpectedEof));
+        }
+        decode_whole(&self.buf)
+    }
+}
+
+/// The companion side of a [`Protocol`]
+pub trait RpcHandler {
+    type Protocol: Protocol;
+
+    fn handle(
+        &mut self,
+        request: <Self::Protocol as Protocol>::Request,
+    ) -> <Self::Protocol as Protocol>::Response;
+}
+
+/// Answers the version handshake, then the requests of a module until it closes the stream.
+///
+/// Returns [`ZygiskError::CompanionVersionMismatch`] once the handshake has been answered, if the
+/// module speaks another version of the protocol.
+pub fn serve<H: RpcHandler>(
+    stream: &mut impl ReadWrite,
+    handler: &mut H,
+) -> Result<(), ZygiskError> {
+    let version = <H::Protocol as Protocol>::VERSION;
+    let mut buf = Vec::new();
+
+    if !read_frame(stream, &mut buf)? {
+        return Err(ZygiskError::CompanionIoError(io::ErrorKind::UnexpectedEof));
+    }
+    let remote = match buf.split_first_chunk::<4>() {
+        Some((magic, rest)) if *magic == MAGIC => decode_whole::<u32>(rest)?,
+        _ => return Err(ZygiskError::CompanionMessageError),
+    };
+
+    buf.clear();
+    buf.extend_from_slice(&version.to_le_bytes());
+    write_frame(stream, &buf)?;
+    if remote != version {
+        return Err(ZygiskError::CompanionVersionMismatch {
+            local: version,
+            remote,
+        });
+    }
+
+    while read_frame(stream, &mut buf)? {
+        let response = handler.handle(decode_whole(&buf)?);
+        buf.clear();
+        response.encode(&mut Encoder::new(&mut buf));
+        write_frame(stream, &buf)?;
+    }
+
+    Ok(())
+}
+
+fn decode_whole<M: Message>(bytes: &[u8]) -> Result<M, ZygiskError> {
+    let mut decoder = Decoder::new(bytes);
+    let message = M::decode(&mut decoder)?;
+    match decoder.is_empty() {
+        true => Ok(message),
+        false => Err(ZygiskError::CompanionMessageError),
+    }
+}
+
+fn write_frame(stream: &mut (impl Write + ?Sized), payload: &[u8]) -> Result<(), ZygiskError> {
+    if payload.len() > MAX_FRAME_LEN {
+        return Err(ZygiskError::CompanionFrameTooLarge(payload.len()));
+    }
+    stream
+        .write_all(&(payload.len() as u32).to_le_bytes())
+        .and_then(|()| stream.write_all(payload))
+        .map_err(|err| ZygiskError::CompanionIoError(err.kind()))
+}
+
+/// Reads a frame into `buf`. Returns `false` on a clean end of stream, before the first byte of a
+/// frame. An end of stream anywhere within a frame is an [`UnexpectedEof`](io::ErrorKind::UnexpectedEof).
+fn read_frame(stream: &mut (impl Read + ?Sized), buf: &mut Vec<u8>) -> Result<bool, ZygiskError> {
+    buf.clear();
+
+    let mut len = [0; 4];
+    let mut received = 0;
+    while received < len.len() {
+        match stream.read(&mut len[received..]) {
+            Ok(0) if received == 0 => return Ok(false),
+            Ok(0) => return Err(ZygiskError::CompanionIoError(io::ErrorKind::UnexpectedEof)),
+            Ok(read) => received += read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(ZygiskError::CompanionIoError(err.kind())),
+        }
+    }
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(ZygiskError::CompanionFrameTooLarge(len));
+    }
+
+    buf.resize(len, 0);
+    stream
+        .read_exact(buf)
+        .map_err(|err| ZygiskError::CompanionIoError(err.kind()))?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, Write},
+        os::unix::net::UnixStream,
+        string::String,
+        thread, vec,
+        vec::Vec,
+    };
+
+    use super::{
+        Decoder, Encoder, MAX_FRAME_LEN, Message, Protocol, RpcClient, RpcHandler, serve,
+        write_frame,
+    };
+    use crate::error::ZygiskError;
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum Request {
+        Ping,
+        Config { package: String, user: u32 },
+    }
+
+    impl Message for Request {
+        fn encode(&self, encoder: &mut Encoder<'_>) {
+            match self {
+                Self::Ping => encoder.put_u8(0),
+                Self::Config { package, user } => {
+                    encoder.put_u8(1);
+                    encoder.put_str(package);
+                    encoder.put_u32(*user);
+                }
+            }
+        }
+
+        fn decode(decoder: &mut Decoder<'_>) -> Result<Self, ZygiskError> {
+            match decoder.get_u8()? {
+                0 => Ok(Self::Ping),
+                1 => Ok(Self::Config {
+                    package: decoder.get()?,
+                    user: decoder.get()?,
+                }),
+                _ => Err(ZygiskError::CompanionMessageError),
+            }
+        }
+    }
+
+    struct V1Protocol;
+
+    impl Protocol for V1Protocol {
+        const VERSION: u32 = 1;
+        type Request = Request;
+        type Response = Option<Vec<u8>>;
+    }
+
+    struct V2Protocol;
+
+    impl Protocol for V2Protocol {
+        const VERSION: u32 = 2;
+        type Request = Request;
+        type Response = Option<Vec<u8>>;
+    }
+
+    struct Handler(Vec<Request>);
+
+    impl RpcHandler for Handler {
+        type Protocol = V1Protocol;
+
+        fn handle(&mut self, request: Request) -> Option<Vec<u8>> {
+            let response = match &request {
+                Request::Ping => None,
+                Request::Config { package, user } => Some(vec![package.len() as u8, *user as u8]),
+            };
+            self.0.push(request);
+            response
+        }
+    }
+
+    #[test]
+    fn encodes_deterministically() {
+        let mut buf = Vec::new();
+        Request::Config {
+            package: "ab".into(),
+            user: 10,
+        }
+        .encode(&mut Encoder::new(&mut buf));
+        assert_eq!(buf, [1, 2, 0, 0, 0, b'a', b'b', 10, 0, 0, 0]);
+
+        let mut decoder = Decoder::new(&buf[..6]);
+        assert!(Request::decode(&mut decoder).is_err());
+        assert!(Decoder::new(&[2]).get_bool().is_err());
+    }
+
+    #[test]
+    fn serves_requests() {
+        let (mut module, mut companion) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || {
+            let mut handler = Handler(Vec::new());
+            serve(&mut companion, &mut handler).map(|()| handler.0)
+        });
+
+        let mut client = RpcClient::<V1Protocol>::connect(&mut module).unwrap();
+        assert_eq!(client.call(&Request::Ping).unwrap(), None);
+        let config = Request::Config {
+            package: "com.example".into(),
+            user: 10,
+        };
+        assert_eq!(client.call(&config).unwrap(), Some(vec![11, 10]));
+        drop(client);
+        drop(module);
+
+        assert_eq!(server.join().unwrap().unwrap(), [Request::Ping, config]);
+    }
+
+    #[test]
+    fn rejects_mismatched_versions() {
+        let (mut module, mut companion) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || serve(&mut companion, &mut Handler(Vec::new())));
+
+        assert!(matches!(
+            RpcClient::<V2Protocol>::connect(&mut module),
+            Err(ZygiskError::CompanionVersionMismatch {
+                local: 2,
+                remote: 1
+            })
+        ));
+        assert!(matches!(
+            server.join().unwrap(),
+            Err(ZygiskError::CompanionVersionMismatch {
+                local: 1,
+                remote: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_truncated_frames() {
+        for bytes in [&[5, 0][..], &[5, 0, 0, 0, 1, 2]] {
+            let (mut module, mut companion) = UnixStream::pair().unwrap();
+            let server = thread::spawn(move || serve(&mut companion, &mut Handler(Vec::new())));
+            drop(RpcClient::<V1Protocol>::connect(&mut module).unwrap());
+
+            module.write_all(bytes).unwrap();
+            drop(module);
+            assert!(matches!(
+                server.join().unwrap(),
+                Err(ZygiskError::CompanionIoError(io::ErrorKind::UnexpectedEof))
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_frames() {
+        let (mut module, _companion) = UnixStream::pair().unwrap();
+        assert!(matches!(
+            write_frame(&mut module, &vec![0; MAX_FRAME_LEN + 1]),
+            Err(ZygiskError::CompanionFrameTooLarge(_))
+        ));
+    }
+}
@@ -1,3 +1,9 @@
+use std::{
+    io,
+    os::fd::RawFd,
+    string::{String, ToString},
+};
+
 #[derive(Clone, Debug, thiserror::Error)]
 pub enum ZygiskError {
     #[error("Unable to connect to the companion process")]
@@ -6,4 +12,44 @@ pub enum ZygiskError {
     UnrecognizedStateFlag(u32),
     #[error("Encountered an error while committing PLT hooks")]
     PltHookCommitError,
+    #[error("Original function of the PLT hook was not found")]
+    PltHookNotFound,
+    #[error("JNI native method {name} with signature {signature} was not found")]
+    JniMethodNotFound { name: String, signature: String },
+    #[error("Invalid JNI method signature {signature:?} (at byte {position})")]
+    InvalidJniSignature { signature: String, position: usize },
+    #[error("JNI call failed ({0})")]
+    JniError(String),
+    #[error("Argument {0} does not exist in this API version")]
+    UnavailableArgument(&'static str),
+    #[error("Argument {0} was not passed by zygote")]
+    MissingArgument(&'static str),
+    #[error("Zygisk refused to exempt file descriptor {0}")]
+    ExemptFdError(RawFd),
+    #[error("Unable to read the process memory map ({0})")]
+    MapsReadError(io::ErrorKind),
+    #[error("Malformed memory map entry at line {0}")]
+    MapsParseError(usize),
+    #[error("Invalid PLT target regex ({0:?})")]
+    InvalidPltRegex(String),
+    #[error("Unable to communicate with the companion ({0})")]
+    CompanionIoError(io::ErrorKind),
+    #[error("Companion protocol version mismatch (local {local}, remote {remote})")]
+    CompanionVersionMismatch { local: u32, remote: u32 },
+    #[error("Companion frame of {0} bytes exceeds the size limit")]
+    CompanionFrameTooLarge(usize),
+    #[error("Malformed companion message")]
+    CompanionMessageError,
+    #[error("Cannot pass {0} file descriptors in a single message")]
+    TooManyFds(usize),
+    #[error("Received file descriptors were truncated (MSG_CTRUNC)")]
+    FdsTruncated,
+    #[error("A global logger has already been installed")]
+    LoggerAlreadyInstalled,
+}
+
+impl From<jni::errors::Error> for ZygiskError {
+    fn from(err: jni::errors::Error) -> Self {
+        Self::JniError(err.to_string())
+    }
 }
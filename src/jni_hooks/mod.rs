@@ -0,0 +1,412 @@
+//! Typed JNI native method hooks.
+//!
+//! [`JniHooks`] owns the class name, method names and signatures of the hooks on a class, and
+//! hands out a [`JniMethod`] handle for each method. Once the hooks are applied, the returned
+//! [`JniHookReport`] lists which methods were found, and gives back their originals with the
+//! type of the replacement. The signature of each method can be derived from the type of the
+//! replacement (see [`signature`]):
+//!
+//! ```no_run
+//! use jni::{JNIEnv, objects::JClass, sys::jint};
+//! use zygisk_api::{
+//!     api::{V5, ZygiskApi},
+//!     jni_hooks::JniHooks,
+//! };
+//!
+//! type GetPidFn = extern "system" fn(JNIEnv<'_>, JClass<'_>) -> jint;
+//!
+//! extern "system" fn get_pid(_: JNIEnv<'_>, _: JClass<'_>) -> jint {
+//!     0
+//! }
+//!
+//! fn hook_process(api: &mut ZygiskApi<'_, V5>, env: JNIEnv<'_>) -> Option<GetPidFn> {
+//!     let mut hooks = JniHooks::new("android/os/Process");
+//!     let method = hooks.native("myPid", zygisk_api::jni_native!(get_pid, fn(JNIEnv, JClass) -> jint));
+//!
+//!     let report = unsafe { hooks.hook(api, env) };
+//!     report.original(method).ok()
+//! }
+//! ```
+
+use core::{
+    ffi::c_void,
+    fmt,
+    marker::PhantomData,
+    mem,
+    ptr::NonNull,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use std::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use jni::{JNIEnv, strings::JNIString, sys::JNINativeMethod};
+
+use crate::{
+    api::HasJniHooks,
+    error::ZygiskError,
+    impl_sealing::Sealed,
+    utils::{ShapeAssertion, impl_fn_ptr},
+};
+
+pub mod signature;
+
+use signature::NativeMethod;
+
+/// Function pointers with the JNI calling convention, whose parameter types do not borrow
+///
+/// Implemented for `extern "system" fn` and `unsafe extern "system" fn` pointers of up to twelve
+/// arguments, such as `extern "system" fn(*mut jni::sys::JNIEnv, jclass) -> jint`. Pointers taking
+/// the lifetime-carrying jni-rs wrappers (`JNIEnv<'_>`, `JString<'_>`, ...) are higher-ranked, and
+/// go through [`jni_native!`](crate::jni_native) instead.
+///
+/// ```compile_fail
+/// let mut hooks = zygisk_api::jni_hooks::JniHooks::new("android/os/Process");
+/// let _ = hooks.method("myPid", "()I", 0usize);
+/// ```
+pub trait NativeFn: Copy + Sealed {
+    #[doc(hidden)]
+    fn into_ptr(self) -> *const ();
+
+    #[doc(hidden)]
+    unsafe fn from_ptr(ptr: *const ()) -> Self;
+}
+
+impl_fn_ptr!(NativeFn, "system");
+
+/// Distinguishes builders, so that a [`JniMethod`] cannot be looked up in another report.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+struct Method {
+    name: String,
+    signature: String,
+    jni_name: JNIString,
+    jni_signature: JNIString,
+    replacement: *mut c_void,
+}
+
+/// A set of hooks on the JNI native methods of a class
+pub struct JniHooks {
+    id: usize,
+    class_name: JNIString,
+    methods: Vec<Method>,
+}
+
+impl JniHooks {
+    /// Create a builder for hooks on the class `class_name`, in its internal form
+    /// (such as `android/os/Process`).
+    pub fn new(class_name: impl AsRef<str>) -> Self {
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            class_name: class_name.into(),
+            methods: Vec::new(),
+        }
+    }
+
+    /// Replace the native method `name` with the JNI `signature` (such as `(I)V`) by `replacement`.
+    ///
+    /// `F` is the type of the native method, an `extern "system" fn` taking the raw `JNIEnv`
+    /// pointer and the receiver first (see [`NativeFn`]). Natives typed with the jni-rs wrappers
+    /// are registered with [`JniHooks::native`].
+    ///
+    /// Returns a handle to look up the original method in the [`JniHookReport`], or
+    /// [`ZygiskError::InvalidJniSignature`] if `signature` is malformed.
+    pub fn method<F: NativeFn>(
+        &mut self,
+        name: impl AsRef<str>,
+        signature: impl AsRef<str>,
+        replacement: F,
+    ) -> Result<JniMethod<F>, ZygiskError> {
+        let signature = signature.as_ref();
+        signature::validate(signature).map_err(|position| ZygiskError::InvalidJniSignature {
+            signature: signature.to_string(),
+            position,
+        })?;
+
+        Ok(self.push(name.as_ref(), signature, replacement.into_ptr()))
+    }
+
+    /// Replace the native method `name` by `native`, built with [`jni_native!`](crate::jni_native).
+    #[inline]
+    pub fn native<F: Copy>(
+        &mut self,
+        name: impl AsRef<str>,
+        native: NativeMethod<F>,
+    ) -> JniMethod<F> {
+        let () = ShapeAssertion::<F, *const ()>::ASSERT;
+
+        let function = native.function();
+        // SAFETY: `jni_native!` only builds natives of `extern "system" fn` types.
+        let function = unsafe { mem::transmute_copy::<F, *const ()>(&function) };
+        self.push(name.as_ref(), native.signature(), function)
+    }
+
+    fn push<F>(&mut self, name: &str, signature: &str, replacement: *const ()) -> JniMethod<F> {
+        self.methods.push(Method {
+            name: name.to_string(),
+            signature: signature.to_string(),
+            jni_name: name.into(),
+            jni_signature: signature.into(),
+            replacement: replacement.cast_mut().cast(),
+        });
+
+        JniMethod {
+            builder: self.id,
+            index: self.methods.len() - 1,
+            _fn: PhantomData,
+        }
+    }
+
+    /// Hook all the methods through `api`, and report which of them were found.
+    ///
+    /// # Safety
+    ///
+    /// A badly designed hook, or a replacement whose type does not match the signature of the
+    /// method, may lead to memory unsafety.
+    pub unsafe fn hook(self, api: &mut impl HasJniHooks, env: JNIEnv) -> JniHookReport {
+        let mut natives = self
+            .methods
+            .iter()
+            .map(|method| JNINativeMethod {
+                name: method.jni_name.as_ptr().cast_mut(),
+                signature: method.jni_signature.as_ptr().cast_mut(),
+                fnPtr: method.replacement,
+            })
+            .collect::<Vec<_>>();
+
+        if !natives.is_empty() {
+            unsafe { api.hook_jni_native_methods(env, &self.class_name, &mut natives) };
+        }
+
+        JniHookReport {
+            builder: self.id,
+            methods: self
+                .methods
+                .into_iter()
+                .zip(natives)
+                .map(|(method, native)| JniHookResult {
+                    name: method.name,
+                    signature: method.signature,
+                    original: NonNull::new(native.fnPtr),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Debug for JniHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JniHooks")
+            .field("class_name", &self.class_name.to_string_lossy())
+            .field(
+                "methods",
+                &self
+                    .methods
+                    .iter()
+                    .map(|method| (&method.name, &method.signature))
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// A handle to a method registered in [`JniHooks`], typed after its replacement
+pub struct JniMethod<F> {
+    builder: usize,
+    index: usize,
+    _fn: PhantomData<F>,
+}
+
+impl<F> Clone for JniMethod<F> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F> Copy for JniMethod<F> {}
+
+impl<F> fmt::Debug for JniMethod<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JniMethod")
+            .field("index", &self.index)
+            .finish()
+    }
+}
+
+/// The outcome of hooking a single method
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JniHookResult {
+    pub name: String,
+    pub signature: String,
+    /// The original function of the method, or [`None`] if the method was not found
+    pub original: Option<NonNull<c_void>>,
+}
+
+impl JniHookResult {
+    #[inline]
+    pub fn is_found(&self) -> bool {
+        self.original.is_some()
+    }
+}
+
+/// The outcome of [`JniHooks::hook`], in the order the methods were registered
+#[derive(Clone, Debug)]
+pub struct JniHookReport {
+    builder: usize,
+    methods: Vec<JniHookResult>,
+}
+
+impl JniHookReport {
+    /// Returns the original function of `method`.
+    ///
+    /// Returns [`ZygiskError::JniMethodNotFound`] if the method was not found.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `method` was registered in another [`JniHooks`].
+    pub fn original<F: Copy>(&self, method: JniMethod<F>) -> Result<F, ZygiskError> {
+        let () = ShapeAssertion::<F, *mut c_void>::ASSERT;
+
+        assert_eq!(
+            method.builder, self.builder,
+            "JniMethod looked up in the report of another JniHooks"
+        );
+
+        let result = &self.methods[method.index];
+        match result.original {
+            // SAFETY: `F` is a function pointer (handles are only created by `JniHooks::method`
+            // and `JniHooks::native`), and the original has the same type as the replacement.
+            Some(original) => {
+                Ok(unsafe { mem::transmute_copy::<*mut c_void, F>(&original.as_ptr()) })
+            }
+            None => Err(ZygiskError::JniMethodNotFound {
+                name: result.name.clone(),
+                signature: result.signature.clone(),
+            }),
+        }
+    }
+
+    /// Returns the outcome for every method.
+    #[inline]
+    pub fn results(&self) -> &[JniHookResult] {
+        &self.methods
+    }
+
+    /// Returns the methods that were not found.
+    pub fn missing(&self) -> impl Iterator<Item = &JniHookResult> {
+        self.methods.iter().filter(|method| !method.is_found())
+    }
+
+    /// Returns `true` if every method was found.
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        self.missing().next().is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ffi::c_void;
+    use std::{vec, vec::Vec};
+
+    use jni::{
+        JNIEnv,
+        objects::JClass,
+        sys::{jclass, jint},
+    };
+
+    use super::JniHooks;
+    use crate::{
+        api::V4,
+        error::ZygiskError,
+        testing::{Call, MockHost},
+    };
+
+    type GetPidFn = extern "system" fn(*mut jni::sys::JNIEnv, jclass) -> jint;
+
+    extern "system" fn original_pid(_: *mut jni::sys::JNIEnv, _: jclass) -> jint {
+        42
+    }
+
+    extern "system" fn get_pid(_: *mut jni::sys::JNIEnv, _: jclass) -> jint {
+        0
+    }
+
+    extern "system" fn get_uid(_: JNIEnv<'_>, _: JClass<'_>) -> jint {
+        0
+    }
+
+    #[test]
+    fn reports_originals() {
+        let mut host = MockHost::<V4>::new();
+        host.set_jni_original(
+            "android/os/Process",
+            "myPid",
+            "()I",
+            original_pid as *mut c_void,
+        );
+
+        let mut hooks = JniHooks::new("android/os/Process");
+        let my_pid = hooks.method::<GetPidFn>("myPid", "()I", get_pid).unwrap();
+        let my_tid = hooks.method::<GetPidFn>("myTid", "()I", get_pid).unwrap();
+        let my_uid = hooks.native(
+            "myUid",
+            crate::jni_native!(get_uid, fn(JNIEnv, JClass) -> jint),
+        );
+
+        let report = unsafe { hooks.hook(&mut host.api(), host.env()) };
+
+        assert_eq!(
+            report.original(my_pid).unwrap() as *const (),
+            original_pid as *const ()
+        );
+        assert!(matches!(
+            report.original(my_tid),
+            Err(ZygiskError::JniMethodNotFound { name, signature })
+                if name == "myTid" && signature == "()I"
+        ));
+        assert!(report.original(my_uid).is_err());
+        assert!(!report.is_complete());
+        assert_eq!(
+            report
+                .missing()
+                .map(|m| m.name.as_str())
+                .collect::<Vec<_>>(),
+            ["myTid", "myUid"]
+        );
+        assert_eq!(
+            host.calls(),
+            [Call::HookJniNativeMethods {
+                class_name: "android/os/Process".into(),
+                methods: vec![
+                    ("myPid".into(), "()I".into()),
+                    ("myTid".into(), "()I".into()),
+                    ("myUid".into(), "()I".into())
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_signatures() {
+        let mut hooks = JniHooks::new("android/os/Process");
+        assert!(matches!(
+            hooks.method::<GetPidFn>("myPid", "(I", get_pid),
+            Err(ZygiskError::InvalidJniSignature { position: 2, .. })
+        ));
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_foreign_handles() {
+        let host = MockHost::<V4>::new();
+
+        let mut first = JniHooks::new("a/B");
+        let method = first.method::<GetPidFn>("myPid", "()I", get_pid).unwrap();
+        let report = unsafe { JniHooks::new("a/B").hook(&mut host.api(), host.env()) };
+
+        let _ = report.original(method);
+    }
+}
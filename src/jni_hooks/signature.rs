@@ -0,0 +1,538 @@
+//! JNI method descriptors.
+//!
+//! A descriptor such as `(Ljava/lang/String;I[B)Z` can either be written by hand and checked at
+//! compile time with [`jni_signature!`](crate::jni_signature), or derived at compile time from the
+//! parameter types of the native function with [`jni_native!`](crate::jni_native), through the
+//! [`JniType`] implementations of the jni-rs types:
+//!
+//! | Rust type | Descriptor |
+//! |-|-|
+//! | `jboolean`, `jbyte`, `jchar`, `jshort`, `jint`, `jlong`, `jfloat`, `jdouble` | `Z`, `B`, `C`, `S`, `I`, `J`, `F`, `D` |
+//! | `JObject`, `JClass`, `JString`, `JThrowable`, `JByteBuffer` | `Ljava/lang/Object;`, `Ljava/lang/Class;`, ... |
+//! | `JObjectArray`, `JByteArray`, `JIntArray`, ... | `[Ljava/lang/Object;`, `[B`, `[I`, ... |
+//! | `()` (return type only) | `V` |
+
+use core::fmt;
+use std::{string::ToString, vec::Vec};
+
+use jni::{
+    objects::{
+        JBooleanArray, JByteArray, JByteBuffer, JCharArray, JClass, JDoubleArray, JFloatArray,
+        JIntArray, JLongArray, JObject, JObjectArray, JShortArray, JString, JThrowable,
+    },
+    sys::{jboolean, jbyte, jchar, jdouble, jfloat, jint, jlong, jobject, jshort},
+};
+
+use crate::error::ZygiskError;
+
+/// Checks that `signature` is a well-formed JNI method descriptor.
+///
+/// Returns the byte offset of the first invalid character otherwise.
+pub const fn validate(signature: &str) -> Result<(), usize> {
+    let bytes = signature.as_bytes();
+
+    if bytes.is_empty() || bytes[0] != b'(' {
+        return Err(0);
+    }
+
+    let mut i = 1;
+    while i < bytes.len() && bytes[i] != b')' {
+        match field_end(bytes, i) {
+            Ok(end) => i = end,
+            Err(position) => return Err(position),
+        }
+    }
+    if i == bytes.len() {
+        return Err(i);
+    }
+    i += 1;
+
+    if i < bytes.len() && bytes[i] == b'V' {
+        i += 1;
+    } else {
+        match field_end(bytes, i) {
+            Ok(end) => i = end,
+            Err(position) => return Err(position),
+        }
+    }
+
+    if i == bytes.len() { Ok(()) } else { Err(i) }
+}
+
+/// Returns the end of the field descriptor starting at `start`, or the offset of the first invalid character.
+const fn field_end(bytes: &[u8], start: usize) -> Result<usize, usize> {
+    let mut i = start;
+    while i < bytes.len() && bytes[i] == b'[' {
+        i += 1;
+    }
+    if i - start > 255 {
+        return Err(start + 255);
+    }
+    if i == bytes.len() {
+        return Err(i);
+    }
+
+    match bytes[i] {
+        b'Z' | b'B' | b'C' | b'S' | b'I' | b'J' | b'F' | b'D' => Ok(i + 1),
+        b'L' => {
+            let name = i + 1;
+            let mut j = name;
+            while j < bytes.len() && bytes[j] != b';' {
+                match bytes[j] {
+                    b'.' | b'[' | b'(' | b')' => return Err(j),
+                    b'/' if j == name || bytes[j - 1] == b'/' => return Err(j),
+                    _ => {}
+                }
+                j += 1;
+            }
+            if j == bytes.len() || j == name || bytes[j - 1] == b'/' {
+                return Err(j);
+            }
+            Ok(j + 1)
+        }
+        _ => Err(i),
+    }
+}
+
+/// Checks a JNI method descriptor at compile time, and evaluates to it.
+///
+/// ```compile_fail
+/// zygisk_api::jni_signature!("(Ljava/lang/String)V");
+/// ```
+#[macro_export]
+macro_rules! jni_signature {
+    ($signature:literal) => {
+        const {
+            match $crate::jni_hooks::signature::validate($signature) {
+                ::core::result::Result::Ok(()) => $signature,
+                ::core::result::Result::Err(_) => {
+                    ::core::panic!(::core::concat!("invalid JNI method signature ", $signature))
+                }
+            }
+        }
+    };
+}
+
+/// A Java type, as it appears in a descriptor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JavaType<'a> {
+    Boolean,
+    Byte,
+    Char,
+    Short,
+    Int,
+    Long,
+    Float,
+    Double,
+    /// An instance of the class with this internal name (such as `java/lang/String`)
+    Object(&'a str),
+    /// An array of the type with this descriptor
+    Array(&'a str),
+}
+
+impl<'a> JavaType<'a> {
+    /// Parse a single, well-formed field descriptor.
+    fn from_descriptor(descriptor: &'a str) -> Self {
+        match descriptor.as_bytes()[0] {
+            b'Z' => Self::Boolean,
+            b'B' => Self::Byte,
+            b'C' => Self::Char,
+            b'S' => Self::Short,
+            b'I' => Self::Int,
+            b'J' => Self::Long,
+            b'F' => Self::Float,
+            b'D' => Self::Double,
+            b'L' => Self::Object(&descriptor[1..descriptor.len() - 1]),
+            _ => Self::Array(&descriptor[1..]),
+        }
+    }
+
+    /// Returns the type of the elements of an array type.
+    #[inline]
+    pub fn component(&self) -> Option<JavaType<'a>> {
+        match *self {
+            Self::Array(component) => Some(Self::from_descriptor(component)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for JavaType<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Boolean => f.write_str("Z"),
+            Self::Byte => f.write_str("B"),
+            Self::Char => f.write_str("C"),
+            Self::Short => f.write_str("S"),
+            Self::Int => f.write_str("I"),
+            Self::Long => f.write_str("J"),
+            Self::Float => f.write_str("F"),
+            Self::Double => f.write_str("D"),
+            Self::Object(class) => write!(f, "L{class};"),
+            Self::Array(component) => write!(f, "[{component}"),
+        }
+    }
+}
+
+/// A parsed JNI method descriptor
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MethodSignature<'a> {
+    pub arguments: Vec<JavaType<'a>>,
+    /// The return type, or [`None`] for `void`
+    pub return_type: Option<JavaType<'a>>,
+}
+
+impl<'a> MethodSignature<'a> {
+    /// Parse a JNI method descriptor.
+    ///
+    /// Returns [`ZygiskError::InvalidJniSignature`] if `signature` is malformed.
+    pub fn parse(signature: &'a str) -> Result<Self, ZygiskError> {
+        validate(signature).map_err(|position| ZygiskError::InvalidJniSignature {
+            signature: signature.to_string(),
+            position,
+        })?;
+
+        let bytes = signature.as_bytes();
+        let mut arguments = Vec::new();
+        let mut i = 1;
+        while bytes[i] != b')' {
+            let Ok(end) = field_end(bytes, i) else {
+                unreachable!()
+            };
+            arguments.push(JavaType::from_descriptor(&signature[i..end]));
+            i = end;
+        }
+
+        let return_type = &signature[i + 1..];
+
+        Ok(Self {
+            arguments,
+            return_type: (return_type != "V").then(|| JavaType::from_descriptor(return_type)),
+        })
+    }
+}
+
+impl fmt::Display for MethodSignature<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("(")?;
+        for argument in &self.arguments {
+            write!(f, "{argument}")?;
+        }
+        f.write_str(")")?;
+        match &self.return_type {
+            Some(return_type) => write!(f, "{return_type}"),
+            None => f.write_str("V"),
+        }
+    }
+}
+
+/// Rust types that can be passed to and returned from native methods
+pub trait JniType {
+    /// The field descriptor of the Java type
+    const DESCRIPTOR: &'static str;
+}
+
+/// Rust types that can be returned from native methods
+pub trait JniReturnType {
+    const DESCRIPTOR: &'static str;
+}
+
+impl JniReturnType for () {
+    const DESCRIPTOR: &'static str = "V";
+}
+
+impl<T: JniType> JniReturnType for T {
+    const DESCRIPTOR: &'static str = T::DESCRIPTOR;
+}
+
+/// Rust types that can receive the `this` object (or class, for static methods) of native methods
+pub trait JniReceiver {}
+
+impl JniReceiver for JObject<'_> {}
+impl JniReceiver for JClass<'_> {}
+impl JniReceiver for jobject {}
+
+macro_rules! impl_jni_type {
+    ($($ty:ty => $descriptor:literal),+ $(,)?) => {
+        $(
+            impl JniType for $ty {
+                const DESCRIPTOR: &'static str = $descriptor;
+            }
+        )+
+    };
+}
+
+impl_jni_type! {
+    jboolean => "Z",
+    jbyte => "B",
+    jchar => "C",
+    jshort => "S",
+    jint => "I",
+    jlong => "J",
+    jfloat => "F",
+    jdouble => "D",
+    JObject<'_> => "Ljava/lang/Object;",
+    JClass<'_> => "Ljava/lang/Class;",
+    JString<'_> => "Ljava/lang/String;",
+    JThrowable<'_> => "Ljava/lang/Throwable;",
+    JByteBuffer<'_> => "Ljava/nio/ByteBuffer;",
+    JObjectArray<'_> => "[Ljava/lang/Object;",
+    JBooleanArray<'_> => "[Z",
+    JByteArray<'_> => "[B",
+    JCharArray<'_> => "[C",
+    JShortArray<'_> => "[S",
+    JIntArray<'_> => "[I",
+    JLongArray<'_> => "[J",
+    JFloatArray<'_> => "[F",
+    JDoubleArray<'_> => "[D",
+}
+
+/// A native method function, along with its JNI descriptor
+///
+/// Built with [`jni_native!`](crate::jni_native), which guarantees that `F` is an
+/// `extern "system" fn` and that the descriptor is well-formed.
+#[derive(Clone, Copy, Debug)]
+pub struct NativeMethod<F> {
+    function: F,
+    signature: &'static str,
+}
+
+impl<F: Copy> NativeMethod<F> {
+    #[doc(hidden)]
+    #[inline(always)]
+    pub const unsafe fn new_unchecked(function: F, signature: &'static str) -> Self {
+        Self {
+            function,
+            signature,
+        }
+    }
+
+    #[inline]
+    pub fn function(&self) -> F {
+        self.function
+    }
+
+    #[inline]
+    pub fn signature(&self) -> &'static str {
+        self.signature
+    }
+}
+
+#[doc(hidden)]
+pub const fn assert_receiver<T: JniReceiver>() {}
+
+#[doc(hidden)]
+pub const fn descriptor_len(parts: &[&str]) -> usize {
+    let mut len = 0;
+    let mut i = 0;
+    while i < parts.len() {
+        len += parts[i].len();
+        i += 1;
+    }
+    len
+}
+
+#[doc(hidden)]
+pub const fn concat_descriptor<const N: usize>(parts: &[&str]) -> [u8; N] {
+    let mut bytes = [0; N];
+    let mut len = 0;
+    let mut i = 0;
+    while i < parts.len() {
+        let part = parts[i].as_bytes();
+        let mut j = 0;
+        while j < part.len() {
+            bytes[len] = part[j];
+            len += 1;
+            j += 1;
+        }
+        i += 1;
+    }
+    bytes
+}
+
+/// Pairs a native method function with the JNI descriptor derived from its parameter types.
+///
+/// The function is cast to an `extern "system" fn` pointer with the given parameter and return
+/// types. Its second parameter is the receiver (a [`JniReceiver`]), which is not part of the descriptor,
+/// and every other parameter and the return type must implement [`JniType`]. The descriptor is
+/// computed at compile time.
+///
+/// A descriptor can also be given after the function type, for parameters whose Java class is
+/// more specific than the Rust type (such as a `JObject` standing for an `android.content.Context`).
+/// It is then checked at compile time, as with [`jni_signature!`](crate::jni_signature).
+///
+/// ```
+/// use jni::{
+///     JNIEnv,
+///     objects::{JByteArray, JClass, JString},
+///     sys::{jboolean, jint},
+/// };
+///
+/// extern "system" fn verify(
+///     _: JNIEnv<'_>,
+///     _: JClass<'_>,
+///     _: JString<'_>,
+///     _: jint,
+///     _: JByteArray<'_>,
+/// ) -> jboolean {
+///     0
+/// }
+///
+/// let native = zygisk_api::jni_native!(
+///     verify,
+///     fn(JNIEnv, JClass, JString, jint, JByteArray) -> jboolean
+/// );
+/// assert_eq!(native.signature(), "(Ljava/lang/String;I[B)Z");
+///
+/// let native = zygisk_api::jni_native!(
+///     verify,
+///     fn(JNIEnv, JClass, JString, jint, JByteArray) -> jboolean,
+///     "(Ljava/lang/CharSequence;I[B)Z"
+/// );
+/// assert_eq!(native.signature(), "(Ljava/lang/CharSequence;I[B)Z");
+/// ```
+#[macro_export]
+macro_rules! jni_native {
+    ($function:expr, fn($env:ty, $receiver:ty $(, $arg:ty)* $(,)?) $(, $signature:literal)?) => {
+        $crate::jni_native!($function, fn($env, $receiver $(, $arg)*) -> () $(, $signature)?)
+    };
+    ($function:expr, fn($env:ty, $receiver:ty $(, $arg:ty)* $(,)?) -> $ret:ty, $signature:literal) => {
+        {
+            let function = $function as extern "system" fn($env, $receiver $(, $arg)*) -> $ret;
+            let signature: &'static str = const {
+                $crate::jni_hooks::signature::assert_receiver::<$receiver>();
+                $crate::jni_signature!($signature)
+            };
+
+            // SAFETY: the function was cast to an `extern "system" fn`, and the signature checked.
+            unsafe { $crate::jni_hooks::signature::NativeMethod::new_unchecked(function, signature) }
+        }
+    };
+    ($function:expr, fn($env:ty, $receiver:ty $(, $arg:ty)* $(,)?) -> $ret:ty) => {
+        {
+            let function = $function as extern "system" fn($env, $receiver $(, $arg)*) -> $ret;
+            let signature: &'static str = const {
+                $crate::jni_hooks::signature::assert_receiver::<$receiver>();
+
+                const PARTS: &[&str] = &[
+                    "(",
+                    $(<$arg as $crate::jni_hooks::signature::JniType>::DESCRIPTOR,)*
+                    ")",
+                    <$ret as $crate::jni_hooks::signature::JniReturnType>::DESCRIPTOR,
+                ];
+                const BYTES: [u8; $crate::jni_hooks::signature::descriptor_len(PARTS)] =
+                    $crate::jni_hooks::signature::concat_descriptor(PARTS);
+
+                match ::core::str::from_utf8(&BYTES) {
+                    ::core::result::Result::Ok(signature) => signature,
+                    ::core::result::Result::Err(_) => ::core::unreachable!(),
+                }
+            };
+
+            // SAFETY: the function was cast to an `extern "system" fn`, and the signature derived
+            // from its type.
+            unsafe { $crate::jni_hooks::signature::NativeMethod::new_unchecked(function, signature) }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::ToString;
+
+    use jni::{
+        JNIEnv,
+        objects::{JClass, JObject, JObjectArray, JString},
+        sys::{jlong, jobject},
+    };
+
+    use super::{JavaType, MethodSignature, validate};
+    use crate::error::ZygiskError;
+
+    #[test]
+    fn validates_signatures() {
+        assert_eq!(validate("()V"), Ok(()));
+        assert_eq!(validate("(Ljava/lang/String;I[B)Z"), Ok(()));
+        assert_eq!(validate("([[Ljava/lang/Object;J)[I"), Ok(()));
+
+        assert_eq!(validate(""), Err(0));
+        assert_eq!(validate("V"), Err(0));
+        assert_eq!(validate("(I"), Err(2));
+        assert_eq!(validate("(V)V"), Err(1));
+        assert_eq!(validate("(Ljava/lang/String)V"), Err(18));
+        assert_eq!(validate("(Ljava.lang.String;)V"), Err(6));
+        assert_eq!(validate("(L;)V"), Err(2));
+        assert_eq!(validate("()"), Err(2));
+        assert_eq!(validate("()VI"), Err(3));
+        assert_eq!(validate("([)V"), Err(2));
+    }
+
+    #[test]
+    fn parses_signatures() {
+        let signature = MethodSignature::parse("(Ljava/lang/String;I[[B)Z").unwrap();
+
+        assert_eq!(
+            signature.arguments,
+            [
+                JavaType::Object("java/lang/String"),
+                JavaType::Int,
+                JavaType::Array("[B"),
+            ]
+        );
+        assert_eq!(
+            signature.arguments[2].component(),
+            Some(JavaType::Array("B"))
+        );
+        assert_eq!(signature.return_type, Some(JavaType::Boolean));
+        assert_eq!(signature.to_string(), "(Ljava/lang/String;I[[B)Z");
+
+        assert_eq!(MethodSignature::parse("()V").unwrap().return_type, None);
+        assert!(matches!(
+            MethodSignature::parse("(Q)V"),
+            Err(ZygiskError::InvalidJniSignature { position: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn derives_signatures() {
+        extern "system" fn noop(_: JNIEnv<'_>, _: JClass<'_>) {}
+        extern "system" fn split(
+            _: JNIEnv<'_>,
+            _: JObject<'_>,
+            _: JString<'_>,
+            _: jlong,
+        ) -> JObjectArray<'static> {
+            unreachable!()
+        }
+        extern "system" fn address(_: JNIEnv<'_>, _: jobject, _: JObject<'_>) -> jlong {
+            0
+        }
+
+        assert_eq!(jni_native!(noop, fn(JNIEnv, JClass)).signature(), "()V");
+        assert_eq!(
+            jni_native!(
+                split,
+                fn(JNIEnv, JObject, JString, jlong) -> JObjectArray<'static>
+            )
+            .signature(),
+            "(Ljava/lang/String;J)[Ljava/lang/Object;"
+        );
+        assert_eq!(
+            jni_native!(address, fn(JNIEnv, jobject, JObject) -> jlong).signature(),
+            "(Ljava/lang/Object;)J"
+        );
+        assert_eq!(
+            jni_native!(
+                address,
+                fn(JNIEnv, jobject, JObject) -> jlong,
+                "(Landroid/content/Context;)J"
+            )
+            .signature(),
+            "(Landroid/content/Context;)J"
+        );
+        assert_eq!(
+            crate::jni_signature!("(Ljava/lang/String;)V"),
+            "(Ljava/lang/String;)V"
+        );
+    }
+}
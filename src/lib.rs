@@ -5,12 +5,24 @@ extern crate std;
 use api::ZygiskApi;
 use jni::JNIEnv;
 use raw::ZygiskRaw;
+use targeting::Targets;
 
 pub mod api;
+pub mod args;
 mod aux;
 pub use aux::*;
+pub mod companion;
 pub mod error;
+pub mod jni_hooks;
+pub mod logger;
+pub mod maps;
+pub mod panics;
+pub mod plt;
+pub mod process;
 pub mod raw;
+pub mod targeting;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
 #[doc(hidden)]
 pub mod utils;
@@ -23,9 +35,26 @@ pub trait ZygiskModule {
     /// The API version that this module is built against
     type Api: for<'a> ZygiskRaw<'a>;
 
+    /// Data carried from a pre-specialization callback to the matching post-specialization callback
+    ///
+    /// A fresh state is created for each specialization and handed to the pre-specialization callback, which can fill it
+    /// in. The post-specialization callback then receives it by value. Modules that do not need any state can use `()`.
+    ///
+    /// This associated type has no default, so every implementation must name it, even if only as `type State = ();`.
+    type State: Default;
+
     /// This method gets called as soon as the Zygisk module gets loaded into the target process
     fn on_load(&self, api: ZygiskApi<'_, Self::Api>, env: JNIEnv<'_>) {}
 
+    /// The app processes that this module applies to, or [`None`] to apply to every app process
+    ///
+    /// This method gets called right before `pre_app_specialize`. If the process does not match the targets,
+    /// the module library gets unloaded after specialization, and the app specialization callbacks are skipped.
+    /// See the [`targeting`] module for details.
+    fn targets(&self) -> Option<&Targets> {
+        None
+    }
+
     /// This method gets called before the target process is specialized as an app process
     ///
     /// At this point, this process just got forked from zygote, but no app-specific specialization process has been done yet.
@@ -39,7 +68,8 @@ pub trait ZygiskModule {
         &self,
         api: ZygiskApi<'a, Self::Api>,
         env: JNIEnv<'a>,
-        args: &'a mut <Self::Api as ZygiskRaw<'_>>::AppSpecializeArgs,
+        args: &'a mut <Self::Api as ZygiskRaw<'a>>::AppSpecializeArgs,
+        state: &mut Self::State,
     ) {
     }
 
@@ -50,7 +80,8 @@ pub trait ZygiskModule {
         &self,
         api: ZygiskApi<'a, Self::Api>,
         env: JNIEnv<'a>,
-        args: &'a <Self::Api as ZygiskRaw<'_>>::AppSpecializeArgs,
+        args: &'a <Self::Api as ZygiskRaw<'a>>::AppSpecializeArgs,
+        state: Self::State,
     ) {
     }
 
@@ -61,7 +92,8 @@ pub trait ZygiskModule {
         &self,
         api: ZygiskApi<'a, Self::Api>,
         env: JNIEnv<'a>,
-        args: &'a mut <Self::Api as ZygiskRaw<'_>>::ServerSpecializeArgs,
+        args: &'a mut <Self::Api as ZygiskRaw<'a>>::ServerSpecializeArgs,
+        state: &mut Self::State,
     ) {
     }
 
@@ -72,11 +104,25 @@ pub trait ZygiskModule {
         &self,
         api: ZygiskApi<'a, Self::Api>,
         env: JNIEnv<'a>,
-        args: &'a <Self::Api as ZygiskRaw<'_>>::ServerSpecializeArgs,
+        args: &'a <Self::Api as ZygiskRaw<'a>>::ServerSpecializeArgs,
+        state: Self::State,
     ) {
     }
 }
 
+/// The module's root companion process
+///
+/// Zygisk starts a single companion process per module, and calls into it from a new thread for each connection made
+/// through `api.with_companion(..)`. The companion is created on the first connection, and then shared by every
+/// connection, which makes it the place to keep caches, loaded configuration or open databases.
+pub trait ZygiskCompanion: Send + Sync + 'static {
+    /// This method gets called once, when the companion process receives its first connection
+    fn init() -> Self;
+
+    /// This method gets called for each connection from the module, possibly concurrently
+    fn handle(&self, stream: &mut std::os::unix::net::UnixStream);
+}
+
 /// Registers a [`ZygiskModule`] implementation as the module's entry point.
 ///
 /// This macro exports a function symbol named `zygisk_module_entry` that Zygisk will use as an entry point to initialize the module.
@@ -90,11 +136,59 @@ pub trait ZygiskModule {
 ///
 /// impl zygisk_api::ZygiskModule for MyModule {
 ///    type Api = zygisk_api::api::V5;
+///    type State = ();
 /// }
 ///
 /// zygisk_api::register_module!(MyModule);
 /// ```
 ///
+/// # API version fallback
+///
+/// A module that is generic over its API version can be registered with a list of versions, in order of preference.
+/// Each version is offered to Zygisk in turn until one is accepted, and only the instance built for the accepted version
+/// gets loaded. This allows a single module to run on Zygisk implementations that predate the preferred API version.
+///
+/// ```
+/// use core::marker::PhantomData;
+///
+/// use zygisk_api::{
+///     ZygiskModule,
+///     api::{HasOptions, V2, V3, V4, V5, ZygiskApi, v1::ZygiskOption},
+///     raw::ZygiskRaw,
+/// };
+///
+/// struct MyModule<V>(PhantomData<V>);
+///
+/// impl<V> Default for MyModule<V> {
+///     fn default() -> Self {
+///         Self(PhantomData)
+///     }
+/// }
+///
+/// impl<V> ZygiskModule for MyModule<V>
+/// where
+///     V: for<'a> ZygiskRaw<'a>,
+///     for<'a> ZygiskApi<'a, V>: HasOptions,
+/// {
+///     type Api = V;
+///     type State = ();
+///
+///     fn on_load(&self, mut api: ZygiskApi<'_, V>, _: jni::JNIEnv<'_>) {
+///         if api.api_version() < 4 {
+///             api.set_option(ZygiskOption::DlCloseModuleLibrary);
+///         }
+///     }
+/// }
+///
+/// zygisk_api::register_module!(MyModule<_>, [V5, V4, V3, V2]);
+/// ```
+///
+/// The module can also be named through a path, as in `register_module!(crate::hooks::MyModule<_>, [V5, V4])`.
+///
+/// Every candidate is built with [`Default::default`] before being offered, so each version that Zygisk rejects still
+/// runs the module's constructor, and leaks the resulting instance. Construction should therefore be cheap and free of
+/// side effects, with any real setup deferred to [`ZygiskModule::on_load`].
+///
 /// If a reference to the entry function is needed, it can be obtained through an `extern "C"` block:
 ///
 /// ```ignore
@@ -108,7 +202,7 @@ pub trait ZygiskModule {
 /// ```
 #[macro_export]
 macro_rules! register_module {
-    ($module:ty) => {
+    (@entry [$($candidate:ty),+]) => {
         const _: () = {
             #[unsafe(export_name = "zygisk_module_entry")]
             unsafe extern "C" fn module_entry(
@@ -118,66 +212,11 @@ macro_rules! register_module {
                 if ::std::panic::catch_unwind(
                     #[inline(always)]
                     move || {
-                        type Api = <$module as $crate::ZygiskModule>::Api;
-                        type RawModule<'a> = $crate::raw::RawModule<'a, Api>;
-                        type ModuleAbi<'a> = $crate::raw::ModuleAbi<'a, Api>;
-
-                        #[repr(transparent)]
-                        struct AssertSyncUnsafeCell<T>(::core::cell::UnsafeCell<T>);
-
-                        unsafe impl<T> Sync for AssertSyncUnsafeCell<T> {}
-
-                        impl<T> AssertSyncUnsafeCell<T> {
-                            #[inline(always)]
-                            const fn new(value: T) -> Self {
-                                Self(::core::cell::UnsafeCell::new(value))
+                        $(
+                            if $crate::register_module!(@register $candidate, api_table, env) {
+                                return;
                             }
-                        }
-
-                        static INSTANCE: AssertSyncUnsafeCell<::core::mem::MaybeUninit<$module>> =
-                            const { AssertSyncUnsafeCell::new(::core::mem::MaybeUninit::uninit()) };
-                        static RAW_MODULE: AssertSyncUnsafeCell<
-                            ::core::mem::MaybeUninit<RawModule<'static>>,
-                        > = AssertSyncUnsafeCell::new(::core::mem::MaybeUninit::uninit());
-                        static MODULE_ABI: AssertSyncUnsafeCell<
-                            ::core::mem::MaybeUninit<ModuleAbi<'static>>,
-                        > = const { AssertSyncUnsafeCell::new(::core::mem::MaybeUninit::uninit()) };
-
-                        unsafe { &mut *INSTANCE.0.get() }
-                            .write(<$module as ::core::default::Default>::default());
-                        let api_table =
-                            unsafe { $crate::raw::ApiTableRef::from_raw(api_table as *const _) };
-
-                        unsafe { &mut *RAW_MODULE.0.get() }.write($crate::raw::RawModule {
-                            dispatch: unsafe { (&*INSTANCE.0.get()).assume_init_ref() },
-                            api_table: ::core::clone::Clone::clone(&api_table),
-                            jni_env: unsafe {
-                                $crate::jni::JNIEnv::from_raw(env).unwrap_unchecked()
-                            },
-                        });
-
-                        unsafe { &mut *MODULE_ABI.0.get() }.write(
-                            <Api as $crate::raw::ZygiskRaw>::abi_from_module(unsafe {
-                                (&mut *RAW_MODULE.0.get()).assume_init_mut()
-                            }),
-                        );
-
-                        let abi = unsafe {
-                            $crate::raw::ModuleAbiRef::from_raw(
-                                (&mut *MODULE_ABI.0.get()).as_mut_ptr(),
-                            )
-                        };
-
-                        if unsafe {
-                            <Api as $crate::raw::ZygiskRaw>::register_module_fn(api_table)(
-                                api_table, abi,
-                            )
-                        } {
-                            unsafe { (&*INSTANCE.0.get()).assume_init_ref() }
-                                .on_load($crate::api::ZygiskApi(api_table), unsafe {
-                                    $crate::jni::JNIEnv::from_raw(env).unwrap_unchecked()
-                                })
-                        }
+                        )+
                     },
                 )
                 .is_err()
@@ -187,16 +226,145 @@ macro_rules! register_module {
             }
         };
     };
+    (@register $module:ty, $api_table:ident, $env:ident) => {{
+        type Api = <$module as $crate::ZygiskModule>::Api;
+        type RawModule<'a> = $crate::raw::RawModule<'a, Api>;
+        type ModuleAbi<'a> = $crate::raw::ModuleAbi<'a, Api>;
+
+        #[repr(transparent)]
+        struct AssertSyncUnsafeCell<T>(::core::cell::UnsafeCell<T>);
+
+        unsafe impl<T> Sync for AssertSyncUnsafeCell<T> {}
+
+        impl<T> AssertSyncUnsafeCell<T> {
+            #[inline(always)]
+            const fn new(value: T) -> Self {
+                Self(::core::cell::UnsafeCell::new(value))
+            }
+        }
+
+        static INSTANCE: AssertSyncUnsafeCell<
+            ::core::mem::MaybeUninit<$crate::raw::Dispatcher<$module>>,
+        > =
+            const { AssertSyncUnsafeCell::new(::core::mem::MaybeUninit::uninit()) };
+        static RAW_MODULE: AssertSyncUnsafeCell<::core::mem::MaybeUninit<RawModule<'static>>> =
+            AssertSyncUnsafeCell::new(::core::mem::MaybeUninit::uninit());
+        static MODULE_ABI: AssertSyncUnsafeCell<::core::mem::MaybeUninit<ModuleAbi<'static>>> =
+            const { AssertSyncUnsafeCell::new(::core::mem::MaybeUninit::uninit()) };
+
+        unsafe { &mut *INSTANCE.0.get() }.write($crate::raw::Dispatcher::new(
+            <$module as ::core::default::Default>::default(),
+        ));
+        let api_table = unsafe { $crate::raw::ApiTableRef::from_raw($api_table as *const _) };
+
+        unsafe { &mut *RAW_MODULE.0.get() }.write($crate::raw::RawModule {
+            dispatch: unsafe { (&*INSTANCE.0.get()).assume_init_ref() },
+            api_table: ::core::clone::Clone::clone(&api_table),
+            jni_env: unsafe { $crate::jni::JNIEnv::from_raw($env).unwrap_unchecked() },
+            untargeted: false,
+        });
+
+        unsafe { &mut *MODULE_ABI.0.get() }.write(<Api as $crate::raw::ZygiskRaw>::abi_from_module(
+            unsafe { (&mut *RAW_MODULE.0.get()).assume_init_mut() },
+        ));
+
+        let abi = unsafe {
+            $crate::raw::ModuleAbiRef::from_raw((&mut *MODULE_ABI.0.get()).as_mut_ptr())
+        };
+
+        let registered = unsafe {
+            <Api as $crate::raw::ZygiskRaw>::register_module_fn(api_table)(api_table, abi)
+        };
+
+        if registered {
+            unsafe { (&mut *RAW_MODULE.0.get()).assume_init_mut() }.on_load();
+        }
+
+        registered
+    }};
+    (@versions [$($path:tt)+] [$($candidate:ty),*] [$version:ident $(, $rest:ident)*]) => {
+        $crate::register_module!(
+            @versions [$($path)+] [$($candidate,)* $($path)+<$crate::api::$version>] [$($rest),*]
+        );
+    };
+    (@versions [$($path:tt)+] [$($candidate:ty),+] []) => {
+        $crate::register_module!(@entry [$($candidate),+]);
+    };
+    ($($segment:ident)::+ <_>, [$($version:ident),+ $(,)?]) => {
+        $crate::register_module!(@versions [$($segment)::+] [] [$($version),+]);
+    };
+    ($module:ty) => {
+        $crate::register_module!(@entry [$module]);
+    };
 }
 
-/// Registers a function that will act as the entry point for the module's companion process
+/// Registers the entry point of the module's companion process
+///
+/// The companion can either be a function with the signature `fn(&mut std::os::unix::net::UnixStream)`, which gets
+/// called for each connection with a `UnixStream` connected to the Zygisk module, or a type implementing
+/// [`ZygiskCompanion`], given as `type MyCompanion`. That type is created lazily through [`ZygiskCompanion::init`],
+/// exactly once even under concurrent connections.
+///
+/// # Example
+///
+/// ```
+/// use std::{collections::HashMap, io::Write, os::unix::net::UnixStream, sync::Mutex};
+///
+/// struct MyCompanion {
+///     configs: Mutex<HashMap<u32, Vec<u8>>>,
+/// }
 ///
-/// The provided function must have the signature `fn(&mut std::os::unix::net::UnixStream)`.
-/// This function will be called when the companion process is started, and it will receive a
-/// `UnixStream` connected to the Zygisk module
+/// impl zygisk_api::ZygiskCompanion for MyCompanion {
+///     fn init() -> Self {
+/// #       INITS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+///         Self { configs: Mutex::new(HashMap::new()) }
+///     }
+///
+///     fn handle(&self, stream: &mut UnixStream) {
+///         let config = self.configs.lock().unwrap().get(&0).cloned().unwrap_or_default();
+///         let _ = stream.write_all(&config);
+///     }
+/// }
+///
+/// zygisk_api::register_companion!(type MyCompanion);
+/// #
+/// # static INITS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+/// #
+/// # unsafe extern "C" {
+/// #     #[link_name = "zygisk_companion_entry"]
+/// #     fn companion_entry(sock_fd: std::os::fd::OwnedFd);
+/// # }
+/// #
+/// # let connections: Vec<_> = (0..4)
+/// #     .map(|_| {
+/// #         let (module, companion) = UnixStream::pair().unwrap();
+/// #         (module, std::thread::spawn(move || unsafe { companion_entry(companion.into()) }))
+/// #     })
+/// #     .collect();
+/// # for (_, thread) in connections {
+/// #     thread.join().unwrap();
+/// # }
+/// # assert_eq!(INITS.load(std::sync::atomic::Ordering::Relaxed), 1);
+/// ```
 #[macro_export]
 macro_rules! register_companion {
+    (type $companion:ty) => {
+        $crate::register_companion!(@entry |stream| {
+            static INSTANCE: ::std::sync::OnceLock<$companion> = ::std::sync::OnceLock::new();
+
+            <$companion as $crate::ZygiskCompanion>::handle(
+                INSTANCE.get_or_init(<$companion as $crate::ZygiskCompanion>::init),
+                stream,
+            )
+        });
+    };
     ($func: expr) => {
+        $crate::register_companion!(@entry |stream| {
+            let func: for<'a> fn(&'a mut ::std::os::unix::net::UnixStream) = $func;
+            func(stream)
+        });
+    };
+    (@entry |$stream:ident| $body:block) => {
         const _: () = {
             #[unsafe(export_name = "zygisk_companion_entry")]
             extern "C" fn companion_entry(sock_fd: ::std::os::fd::OwnedFd) {
@@ -208,8 +376,8 @@ macro_rules! register_companion {
                                 ::std::os::fd::OwnedFd,
                             >>::from(sock_fd);
 
-                        let func: for<'a> fn(&'a mut ::std::os::unix::net::UnixStream) = $func;
-                        func(&mut stream)
+                        let $stream = &mut stream;
+                        $body
                     },
                 )
                 .is_err()
@@ -223,15 +391,60 @@ macro_rules! register_companion {
 
 #[cfg(test)]
 mod compile_test {
-    use crate::{ZygiskModule, api};
+    use core::marker::PhantomData;
+
+    use crate::{
+        ZygiskModule,
+        api::{self, HasOptions, ZygiskApi, v1::ZygiskOption},
+        raw::ZygiskRaw,
+        testing::{Call, MockHost},
+    };
 
-    #[derive(Default)]
-    struct MyModule;
+    struct MyModule<V>(PhantomData<V>);
 
-    impl ZygiskModule for MyModule {
-        type Api = api::V5;
+    impl<V> Default for MyModule<V> {
+        fn default() -> Self {
+            Self(PhantomData)
+        }
     }
-    register_module!(MyModule);
+
+    impl<V> ZygiskModule for MyModule<V>
+    where
+        V: for<'a> ZygiskRaw<'a>,
+        for<'a> ZygiskApi<'a, V>: HasOptions,
+    {
+        type Api = V;
+        type State = ();
+
+        fn on_load(&self, mut api: ZygiskApi<'_, V>, _: jni::JNIEnv<'_>) {
+            if api.api_version() < 4 {
+                api.set_option(ZygiskOption::DlCloseModuleLibrary);
+            }
+        }
+    }
+    register_module!(crate::compile_test::MyModule<_>, [V5, V4, V3]);
 
     register_companion!(|_| ());
+
+    unsafe extern "C" {
+        #[link_name = "zygisk_module_entry"]
+        fn module_entry(api_table: *const (), env: *mut jni::sys::JNIEnv);
+    }
+
+    #[test]
+    fn falls_back_to_older_api_versions() {
+        let mut host = MockHost::<api::V3>::new();
+        host.set_max_api_version(3);
+
+        assert!(unsafe { host.enter(module_entry) });
+        assert_eq!(
+            host.calls(),
+            [
+                Call::RegisterModule { api_version: 5 },
+                Call::RegisterModule { api_version: 4 },
+                Call::RegisterModule { api_version: 3 },
+                Call::SetOption(ZygiskOption::DlCloseModuleLibrary),
+            ]
+        );
+    }
 }
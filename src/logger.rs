@@ -0,0 +1,427 @@
+//! A [`log`] backend writing to logcat.
+//!
+//! The Android logging functions are resolved at runtime, so that the same module binary logs to
+//! the standard error stream when run outside of Android (such as in unit tests). Records are
+//! prefixed with the lifecycle [`Phase`] the module is in, and with the name of the process once
+//! it is known:
+//!
+//! ```text
+//! I MyModule: [pre_app com.example.app:remote] patching config
+//! ```
+//!
+//! The logger is meant to be installed from [`ZygiskModule::on_load`](crate::ZygiskModule::on_load):
+//!
+//! ```
+//! use zygisk_api::{ZygiskModule, api::{V5, ZygiskApi}, logger::Logger};
+//!
+//! #[derive(Default)]
+//! struct MyModule;
+//!
+//! impl ZygiskModule for MyModule {
+//!     type Api = V5;
+//!     type State = ();
+//!
+//!     fn on_load(&self, _: ZygiskApi<'_, V5>, _: jni::JNIEnv<'_>) {
+//!         let _ = Logger::new("MyModule")
+//!             .with_level(log::LevelFilter::Debug)
+//!             .install();
+//!         log::info!("loaded");
+//!     }
+//! }
+//! ```
+
+use core::{
+    cell::Cell,
+    ffi::{CStr, c_void},
+    fmt, mem,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use std::{
+    boxed::Box,
+    ffi::CString,
+    io::Write,
+    string::{String, ToString},
+    sync::{OnceLock, RwLock},
+};
+
+use libc::{c_char, c_int};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::error::ZygiskError;
+
+/// The lifecycle callback that the module is being called through
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Phase {
+    OnLoad = 1,
+    PreAppSpecialize = 2,
+    PostAppSpecialize = 3,
+    PreServerSpecialize = 4,
+    PostServerSpecialize = 5,
+}
+
+impl Phase {
+    /// Returns the name of the [`ZygiskModule`](crate::ZygiskModule) method of this phase.
+    pub const fn callback(self) -> &'static str {
+        match self {
+            Self::OnLoad => "on_load",
+            Self::PreAppSpecialize => "pre_app_specialize",
+            Self::PostAppSpecialize => "post_app_specialize",
+            Self::PreServerSpecialize => "pre_server_specialize",
+            Self::PostServerSpecialize => "post_server_specialize",
+        }
+    }
+
+    /// Returns the phase whose callback is running on this thread, if any.
+    pub fn current() -> Option<Self> {
+        PHASE.get()
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::OnLoad => "on_load",
+            Self::PreAppSpecialize => "pre_app",
+            Self::PostAppSpecialize => "post_app",
+            Self::PreServerSpecialize => "pre_server",
+            Self::PostServerSpecialize => "post_server",
+        })
+    }
+}
+
+/// The logcat buffer that records are written to
+///
+/// The events buffer is left out, as it holds binary payloads rather than text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(i32)]
+pub enum LogBuffer {
+    #[default]
+    Main = 0,
+    Radio = 1,
+    System = 3,
+    Crash = 4,
+}
+
+/// A [`log::Log`] implementation writing to logcat, or to the standard error stream outside of
+/// Android
+pub struct Logger {
+    tag: CString,
+    level: LevelFilter,
+    buffer: LogBuffer,
+}
+
+impl Logger {
+    /// Creates a logger writing to the main buffer with `tag`, at the [`LevelFilter::Info`] level.
+    pub fn new(tag: &str) -> Self {
+        Self {
+            tag: c_string(tag),
+            level: LevelFilter::Info,
+            buffer: LogBuffer::Main,
+        }
+    }
+
+    pub fn with_level(mut self, level: LevelFilter) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets the logcat buffer to write to. Only the main buffer is available on Android versions
+    /// lacking `__android_log_buf_write`.
+    pub fn with_buffer(mut self, buffer: LogBuffer) -> Self {
+        self.buffer = buffer;
+        self
+    }
+
+    /// Installs this logger as the global logger of the [`log`] facade.
+    ///
+    /// Returns [`ZygiskError::LoggerAlreadyInstalled`] if a global logger has already been
+    /// installed.
+    pub fn install(self) -> Result<(), ZygiskError> {
+        let level = self.level;
+        log::set_boxed_logger(Box::new(self)).map_err(|_| ZygiskError::LoggerAlreadyInstalled)?;
+        log::set_max_level(level);
+        INSTALLED.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let process = PROCESS_NAME.read().unwrap_or_else(|err| err.into_inner());
+        let message = format_message(Phase::current(), process.as_deref(), record.args());
+        drop(process);
+
+        match backend() {
+            Backend::BufWrite(write) => unsafe {
+                write(
+                    self.buffer as c_int,
+                    priority(record.level()),
+                    self.tag.as_ptr(),
+                    c_string(&message).as_ptr(),
+                );
+            },
+            Backend::Write(write) => unsafe {
+                write(
+                    priority(record.level()),
+                    self.tag.as_ptr(),
+                    c_string(&message).as_ptr(),
+                );
+            },
+            Backend::Stderr => {
+                let _ = write_line(&mut std::io::stderr(), &self.tag, record.level(), &message);
+            }
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+static PROCESS_NAME: RwLock<Option<String>> = RwLock::new(None);
+
+std::thread_local! {
+    /// The phase of the callback running on this thread, which Zygisk calls on its main thread
+    static PHASE: Cell<Option<Phase>> = const { Cell::new(None) };
+}
+
+/// Records that the module entered `phase`, until the returned guard is dropped.
+pub(crate) fn enter_phase(phase: Phase) -> PhaseGuard {
+    PhaseGuard(PHASE.replace(Some(phase)))
+}
+
+/// Restores the phase that was current before [`enter_phase`] when dropped
+pub(crate) struct PhaseGuard(Option<Phase>);
+
+impl Drop for PhaseGuard {
+    fn drop(&mut self) {
+        PHASE.set(self.0);
+    }
+}
+
+/// Returns whether a [`Logger`] has been installed, so that the process name is worth decoding.
+pub(crate) fn is_installed() -> bool {
+    INSTALLED.load(Ordering::Relaxed)
+}
+
+pub(crate) fn set_process_name(name: impl Into<String>) {
+    *PROCESS_NAME.write().unwrap_or_else(|err| err.into_inner()) = Some(name.into());
+}
+
+fn format_message(
+    phase: Option<Phase>,
+    process: Option<&str>,
+    args: &fmt::Arguments<'_>,
+) -> String {
+    match (phase, process) {
+        (Some(phase), Some(process)) => std::format!("[{phase} {process}] {args}"),
+        (Some(phase), None) => std::format!("[{phase}] {args}"),
+        (None, Some(process)) => std::format!("[{process}] {args}"),
+        (None, None) => args.to_string(),
+    }
+}
+
+/// Writes a record in the format of `logcat -v brief`, without the pid.
+fn write_line(
+    out: &mut impl Write,
+    tag: &CStr,
+    level: Level,
+    message: &str,
+) -> std::io::Result<()> {
+    std::writeln!(
+        out,
+        "{} {}: {message}",
+        level_letter(level),
+        tag.to_string_lossy()
+    )
+}
+
+type LogBufWrite = unsafe extern "C" fn(c_int, c_int, *const c_char, *const c_char) -> c_int;
+type LogWrite = unsafe extern "C" fn(c_int, *const c_char, *const c_char) -> c_int;
+
+#[derive(Clone, Copy)]
+enum Backend {
+    BufWrite(LogBufWrite),
+    Write(LogWrite),
+    Stderr,
+}
+
+fn backend() -> Backend {
+    static BACKEND: OnceLock<Backend> = OnceLock::new();
+
+    *BACKEND.get_or_init(|| {
+        if let Some(write) = lookup(c"__android_log_buf_write") {
+            Backend::BufWrite(unsafe { mem::transmute::<*mut c_void, LogBufWrite>(write) })
+        } else if let Some(write) = lookup(c"__android_log_write") {
+            Backend::Write(unsafe { mem::transmute::<*mut c_void, LogWrite>(write) })
+        } else {
+            Backend::Stderr
+        }
+    })
+}
+
+fn lookup(symbol: &CStr) -> Option<*mut c_void> {
+    let ptr = unsafe { libc::dlsym(libc::RTLD_DEFAULT, symbol.as_ptr()) };
+    (!ptr.is_null()).then_some(ptr)
+}
+
+/// Returns the `android_LogPriority` of `level`.
+fn priority(level: Level) -> c_int {
+    match level {
+        Level::Trace => 2,
+        Level::Debug => 3,
+        Level::Info => 4,
+        Level::Warn => 5,
+        Level::Error => 6,
+    }
+}
+
+fn level_letter(level: Level) -> char {
+    match level {
+        Level::Trace => 'V',
+        Level::Debug => 'D',
+        Level::Info => 'I',
+        Level::Warn => 'W',
+        Level::Error => 'E',
+    }
+}
+
+/// Converts `string` for logcat, dropping interior nul bytes.
+fn c_string(string: &str) -> CString {
+    CString::new(string.replace('\0', "")).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use std::{rc::Rc, vec::Vec};
+
+    use jni::JNIEnv;
+    use log::{Level, LevelFilter, Log, Metadata};
+
+    use super::{
+        Backend, Logger, Phase, backend, c_string, format_message, is_installed, write_line,
+    };
+    use crate::{
+        ZygiskModule,
+        api::{V5, ZygiskApi},
+        error::ZygiskError,
+        raw::ZygiskRaw,
+        testing::AppFixture,
+    };
+
+    #[test]
+    fn formats_messages() {
+        let args = format_args!("patched {} methods", 3);
+        assert_eq!(
+            format_message(
+                Some(Phase::PreAppSpecialize),
+                Some("com.example:remote"),
+                &args
+            ),
+            "[pre_app com.example:remote] patched 3 methods"
+        );
+        assert_eq!(
+            format_message(Some(Phase::OnLoad), None, &format_args!("loaded")),
+            "[on_load] loaded"
+        );
+        assert_eq!(
+            format_message(None, Some("system_server"), &format_args!("ready")),
+            "[system_server] ready"
+        );
+        assert_eq!(format_message(None, None, &format_args!("idle")), "idle");
+    }
+
+    #[test]
+    fn strips_nul_bytes() {
+        assert_eq!(c_string("a\0b").to_bytes(), b"ab");
+    }
+
+    #[test]
+    fn names_phase_callbacks() {
+        assert_eq!(Phase::OnLoad.callback(), "on_load");
+        assert_eq!(
+            Phase::PostServerSpecialize.callback(),
+            "post_server_specialize"
+        );
+    }
+
+    #[derive(Default)]
+    struct RecordsPhase {
+        seen: Rc<Cell<Option<Phase>>>,
+    }
+
+    impl ZygiskModule for RecordsPhase {
+        type Api = V5;
+        type State = ();
+
+        fn pre_app_specialize<'a>(
+            &self,
+            _: ZygiskApi<'a, V5>,
+            _: JNIEnv<'a>,
+            _: &'a mut <V5 as ZygiskRaw<'a>>::AppSpecializeArgs,
+            _: &mut (),
+        ) {
+            self.seen.set(Phase::current());
+        }
+    }
+
+    #[test]
+    fn leaves_phases_after_callbacks() {
+        let mut fixture = AppFixture::<V5>::new("com.example");
+        let seen = Rc::new(Cell::new(None));
+        assert!(fixture.host.load(RecordsPhase {
+            seen: Rc::clone(&seen),
+        }));
+        fixture.specialize();
+
+        assert_eq!(seen.get(), Some(Phase::PreAppSpecialize));
+        assert_eq!(Phase::current(), None);
+    }
+
+    #[test]
+    fn filters_levels() {
+        let logger = Logger::new("MyModule").with_level(LevelFilter::Warn);
+        let metadata = |level| Metadata::builder().level(level).build();
+
+        assert!(logger.enabled(&metadata(Level::Error)));
+        assert!(logger.enabled(&metadata(Level::Warn)));
+        assert!(!logger.enabled(&metadata(Level::Info)));
+        assert!(!Logger::new("MyModule").enabled(&metadata(Level::Debug)));
+    }
+
+    #[test]
+    fn falls_back_to_stderr() {
+        // liblog is not available outside of Android
+        assert!(matches!(backend(), Backend::Stderr));
+
+        let mut out = Vec::new();
+        write_line(&mut out, c"MyModule", Level::Warn, "[on_load] loaded").unwrap();
+        assert_eq!(out, b"W MyModule: [on_load] loaded\n");
+    }
+
+    // The only test installing the global logger, which can be installed once per process
+    #[test]
+    fn installs_once() {
+        Logger::new("MyModule")
+            .with_level(LevelFilter::Debug)
+            .install()
+            .unwrap();
+        assert!(is_installed());
+        assert_eq!(log::max_level(), LevelFilter::Debug);
+
+        assert!(matches!(
+            Logger::new("MyModule").install(),
+            Err(ZygiskError::LoggerAlreadyInstalled)
+        ));
+        assert_eq!(log::max_level(), LevelFilter::Debug);
+    }
+}
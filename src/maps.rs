@@ -0,0 +1,297 @@
+//! Parser for the memory map of a process, as exposed by `/proc/[PID]/maps`.
+//!
+//! From API v4 onwards, PLT hooks are registered against the device and inode numbers of the
+//! mapped ELF instead of a path regex. [`MemoryMap`] provides these pairs:
+//!
+//! ```no_run
+//! use zygisk_api::{api::V5, api::ZygiskApi, maps::MemoryMap};
+//!
+//! fn hook_libc(api: &mut ZygiskApi<'_, V5>, replacement: *const (), original: &mut *const ()) {
+//!     let map = MemoryMap::current().unwrap();
+//!
+//!     for (device, inode) in map.files_with_suffix("/libc.so") {
+//!         unsafe { api.plt_hook_register(device, inode, c"open", replacement, original) };
+//!     }
+//! }
+//! ```
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use libc::{dev_t, ino_t};
+
+use crate::error::ZygiskError;
+
+bitflags::bitflags! {
+    /// Access permissions of a memory mapping
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct MapPermissions: u8 {
+        const READ = (1 << 0);
+        const WRITE = (1 << 1);
+        const EXECUTE = (1 << 2);
+        /// The mapping is shared rather than private (copy-on-write)
+        const SHARED = (1 << 3);
+    }
+}
+
+/// A single line of `/proc/[PID]/maps`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MapEntry {
+    pub start: usize,
+    pub end: usize,
+    pub permissions: MapPermissions,
+    pub offset: u64,
+    pub device: dev_t,
+    pub inode: ino_t,
+    /// The backing file or pseudo-path (such as `[stack]`) of the mapping, if any. Bytes that are
+    /// not valid UTF-8 are replaced with `U+FFFD`.
+    pub path: Option<String>,
+}
+
+impl MapEntry {
+    /// Returns the `(device, inode)` pair identifying the file backing this mapping, or [`None`]
+    /// for anonymous mappings.
+    #[inline]
+    pub fn file_id(&self) -> Option<(dev_t, ino_t)> {
+        (self.inode != 0).then_some((self.device, self.inode))
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let mut rest = line;
+        let mut field = || {
+            let trimmed = rest.trim_start_matches(' ');
+            let end = trimmed.find(' ').unwrap_or(trimmed.len());
+            let (field, remainder) = trimmed.split_at(end);
+            rest = remainder;
+            (!field.is_empty()).then_some(field)
+        };
+
+        let (start, end) = field()?.split_once('-')?;
+        let perms = field()?.as_bytes();
+        let offset = field()?;
+        let (major, minor) = field()?.split_once(':')?;
+        let inode = field()?;
+
+        if perms.len() != 4 {
+            return None;
+        }
+        let mut permissions = MapPermissions::empty();
+        for (byte, unset, set, flag) in [
+            (perms[0], b'-', b'r', MapPermissions::READ),
+            (perms[1], b'-', b'w', MapPermissions::WRITE),
+            (perms[2], b'-', b'x', MapPermissions::EXECUTE),
+            (perms[3], b'p', b's', MapPermissions::SHARED),
+        ] {
+            match byte {
+                byte if byte == unset => {}
+                byte if byte == set => permissions |= flag,
+                _ => return None,
+            }
+        }
+
+        let path = rest.trim_start_matches(' ');
+
+        Some(Self {
+            start: usize::from_str_radix(start, 16).ok()?,
+            end: usize::from_str_radix(end, 16).ok()?,
+            permissions,
+            offset: u64::from_str_radix(offset, 16).ok()?,
+            device: libc::makedev(
+                u32::from_str_radix(major, 16).ok()?,
+                u32::from_str_radix(minor, 16).ok()?,
+            ),
+            inode: inode.parse().ok()?,
+            path: (!path.is_empty()).then(|| path.to_string()),
+        })
+    }
+}
+
+/// The memory map of a process
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryMap {
+    entries: Vec<MapEntry>,
+}
+
+impl MemoryMap {
+    /// Read the memory map of the current process from `/proc/self/maps`.
+    pub fn current() -> Result<Self, ZygiskError> {
+        let file =
+            File::open("/proc/self/maps").map_err(|err| ZygiskError::MapsReadError(err.kind()))?;
+
+        Self::parse(file)
+    }
+
+    /// Parse a memory map in the format of `/proc/[PID]/maps`.
+    ///
+    /// Returns [`ZygiskError::MapsParseError`] with the (1-based) line number of the first malformed line.
+    pub fn parse(reader: impl Read) -> Result<Self, ZygiskError> {
+        let mut entries = Vec::new();
+
+        for (index, line) in BufReader::new(reader).split(b'\n').enumerate() {
+            let line = line.map_err(|err| ZygiskError::MapsReadError(err.kind()))?;
+            if line.is_empty() {
+                continue;
+            }
+
+            // Mapped paths are arbitrary bytes, which must not fail the whole map
+            let entry = MapEntry::parse(&String::from_utf8_lossy(&line))
+                .ok_or(ZygiskError::MapsParseError(index + 1))?;
+            entries.push(entry);
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Returns all the mappings, in address order.
+    #[inline]
+    pub fn entries(&self) -> &[MapEntry] {
+        &self.entries
+    }
+
+    /// Returns the mappings whose path ends with `suffix`.
+    pub fn find_by_path_suffix<'a>(
+        &'a self,
+        suffix: &'a str,
+    ) -> impl Iterator<Item = &'a MapEntry> + 'a {
+        self.entries.iter().filter(move |entry| {
+            entry
+                .path
+                .as_deref()
+                .is_some_and(|path| path.ends_with(suffix))
+        })
+    }
+
+    /// Returns the distinct `(device, inode)` pairs of the files mapped from a path ending with
+    /// `suffix`, ready to be passed to `plt_hook_register`.
+    pub fn files_with_suffix(&self, suffix: &str) -> Vec<(dev_t, ino_t)> {
+        self.files_matching(|entry| {
+            entry
+                .path
+                .as_deref()
+                .is_some_and(|path| path.ends_with(suffix))
+        })
+    }
+
+    /// Returns the distinct `(device, inode)` pairs of the files backing the mappings for which
+    /// `predicate` returns `true`.
+    pub fn files_matching(
+        &self,
+        mut predicate: impl FnMut(&MapEntry) -> bool,
+    ) -> Vec<(dev_t, ino_t)> {
+        let mut files = Vec::new();
+
+        for entry in &self.entries {
+            if let Some(id) = entry.file_id()
+                && !files.contains(&id)
+                && predicate(entry)
+            {
+                files.push(id);
+            }
+        }
+
+        files
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MapEntry, MapPermissions, MemoryMap};
+    use crate::error::ZygiskError;
+
+    const FIXTURE: &str = "\
+56b4346000-56b4347000 r-xp 00002000 fe:00 235                        /system/bin/app_process64
+7a1c000000-7a1c040000 r--p 00000000 07:38 29                         /apex/com.android.runtime/lib64/bionic/libc.so
+7a1c040000-7a1c0e0000 r-xp 00040000 07:38 29                         /apex/com.android.runtime/lib64/bionic/libc.so
+7a1d000000-7a1d001000 rw-s 00000000 00:05 1024                       /dev/ashmem/dalvik-zygote space (deleted)
+7ffd0000-7ffd2000 rw-p 00000000 00:00 0                              [stack]
+7ffe0000-7ffe1000 ---p 00000000 00:00 0
+";
+
+    #[test]
+    fn parses_entries() {
+        let map = MemoryMap::parse(FIXTURE.as_bytes()).unwrap();
+
+        assert_eq!(map.entries().len(), 6);
+        assert_eq!(
+            map.entries()[0],
+            MapEntry {
+                start: 0x56b4346000,
+                end: 0x56b4347000,
+                permissions: MapPermissions::READ | MapPermissions::EXECUTE,
+                offset: 0x2000,
+                device: libc::makedev(0xfe, 0),
+                inode: 235,
+                path: Some("/system/bin/app_process64".into()),
+            }
+        );
+        assert_eq!(
+            map.entries()[3].path.as_deref(),
+            Some("/dev/ashmem/dalvik-zygote space (deleted)")
+        );
+        assert!(
+            map.entries()[3]
+                .permissions
+                .contains(MapPermissions::SHARED)
+        );
+        assert_eq!(map.entries()[4].file_id(), None);
+        assert_eq!(map.entries()[5].path, None);
+    }
+
+    #[test]
+    fn finds_files() {
+        let map = MemoryMap::parse(FIXTURE.as_bytes()).unwrap();
+
+        assert_eq!(map.find_by_path_suffix("/libc.so").count(), 2);
+        assert_eq!(
+            map.files_with_suffix("/libc.so"),
+            [(libc::makedev(7, 0x38), 29)]
+        );
+        assert!(map.files_with_suffix("[stack]").is_empty());
+    }
+
+    #[test]
+    fn reports_malformed_lines() {
+        let err = MemoryMap::parse("7ffe0000-7ffe1000 ---p 00000000 00:00 0\nbogus\n".as_bytes());
+
+        assert!(matches!(err, Err(ZygiskError::MapsParseError(2))));
+    }
+
+    #[test]
+    fn checks_permission_columns() {
+        for perms in ["rwxp", "---s", "r-xs"] {
+            let line = std::format!("7ffe0000-7ffe1000 {perms} 00000000 00:00 0");
+            assert!(MapEntry::parse(&line).is_some(), "{perms}");
+        }
+        for perms in ["p--p", "r-x-", "rw-r", "-wrp"] {
+            let line = std::format!("7ffe0000-7ffe1000 {perms} 00000000 00:00 0");
+            assert!(MapEntry::parse(&line).is_none(), "{perms}");
+        }
+    }
+
+    #[test]
+    fn tolerates_non_utf8_paths() {
+        let mut maps = FIXTURE.as_bytes().to_vec();
+        maps.extend_from_slice(
+            b"7a1e000000-7a1e001000 r--p 00000000 07:38 30   /data/app/\xff\xfe.so\n",
+        );
+
+        let map = MemoryMap::parse(maps.as_slice()).unwrap();
+        assert_eq!(map.entries().len(), 7);
+        assert_eq!(
+            map.entries()[6].path.as_deref(),
+            Some("/data/app/\u{fffd}\u{fffd}.so")
+        );
+        assert_eq!(map.files_with_suffix(".so").len(), 2);
+    }
+
+    #[test]
+    fn reads_current_process() {
+        let map = MemoryMap::current().unwrap();
+
+        assert!(map.entries().iter().any(|entry| entry.file_id().is_some()));
+    }
+}
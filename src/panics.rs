@@ -0,0 +1,298 @@
+//! Handling of panics raised by the module's lifecycle callbacks.
+//!
+//! Zygisk calls into the module through `extern "C"` functions, which cannot unwind. Every
+//! callback, from [`on_load`](crate::ZygiskModule::on_load) to the specialization callbacks, is
+//! therefore run under [`catch_unwind`](std::panic::catch_unwind): a panic is reported to the panic
+//! sink, and then handled according to the [`PanicPolicy`].
+//!
+//! ```
+//! use zygisk_api::panics::{self, PanicPolicy};
+//!
+//! panics::set_panic_policy(PanicPolicy::ContinueAndUnload);
+//! panics::set_panic_sink(|report| {
+//!     let _ = std::fs::write("/data/local/tmp/my_module_panic.txt", report.to_string());
+//! });
+//! ```
+
+use core::{
+    any::Any,
+    cell::{Cell, RefCell},
+    fmt,
+    panic::AssertUnwindSafe,
+    sync::atomic::{AtomicU8, Ordering},
+};
+use std::{
+    boxed::Box,
+    io::Write,
+    panic,
+    string::{String, ToString},
+    sync::{Once, RwLock},
+};
+
+use crate::logger;
+
+/// What to do once a panic of a lifecycle callback has been reported
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PanicPolicy {
+    /// Abort the process, as an unwinding `extern "C"` function would
+    #[default]
+    Abort = 0,
+    /// Return to Zygisk as if the callback had completed
+    Continue = 1,
+    /// Return to Zygisk, and request the module library to be unloaded after specialization
+    ContinueAndUnload = 2,
+}
+
+/// A panic caught at the boundary of a lifecycle callback
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PanicReport {
+    /// The name of the callback, such as `pre_app_specialize`
+    pub callback: &'static str,
+    pub message: String,
+    /// `file:line:column` of the panic, if known
+    pub location: Option<String>,
+}
+
+impl fmt::Display for PanicReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "module panicked in {}", self.callback)?;
+        if let Some(location) = &self.location {
+            write!(f, " at {location}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+type PanicSink = Box<dyn Fn(&PanicReport) + Send + Sync>;
+
+static POLICY: AtomicU8 = AtomicU8::new(PanicPolicy::Abort as u8);
+static SINK: RwLock<Option<PanicSink>> = RwLock::new(None);
+static HOOK: Once = Once::new();
+
+std::thread_local! {
+    /// Number of callbacks being run under [`catch`] on this thread
+    static GUARDS: Cell<usize> = const { Cell::new(0) };
+    static LOCATION: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Sets the policy applied after a panic has been reported. Defaults to [`PanicPolicy::Abort`].
+pub fn set_panic_policy(policy: PanicPolicy) {
+    POLICY.store(policy as u8, Ordering::Relaxed);
+}
+
+pub fn panic_policy() -> PanicPolicy {
+    match POLICY.load(Ordering::Relaxed) {
+        1 => PanicPolicy::Continue,
+        2 => PanicPolicy::ContinueAndUnload,
+        _ => PanicPolicy::Abort,
+    }
+}
+
+/// Sets the function that panics of lifecycle callbacks are reported to. By default, reports are
+/// logged through [`log::error!`] once a [`Logger`](crate::logger::Logger) has been installed, and
+/// written to the standard error stream otherwise.
+pub fn set_panic_sink(sink: impl Fn(&PanicReport) + Send + Sync + 'static) {
+    *SINK.write().unwrap_or_else(|err| err.into_inner()) = Some(Box::new(sink));
+}
+
+/// Runs the `callback` named `name`, catching and reporting any panic.
+///
+/// Returns the policy to apply if `callback` panicked. Aborts right away under
+/// [`PanicPolicy::Abort`].
+pub(crate) fn catch(name: &'static str, callback: impl FnOnce()) -> Option<PanicPolicy> {
+    install_hook();
+
+    GUARDS.set(GUARDS.get() + 1);
+    let result = panic::catch_unwind(AssertUnwindSafe(callback));
+    GUARDS.set(GUARDS.get() - 1);
+
+    let payload = result.err()?;
+    report(&PanicReport {
+        callback: name,
+        message: payload_message(&*payload),
+        location: LOCATION.take(),
+    });
+    // The payload may panic while being dropped
+    if panic::catch_unwind(AssertUnwindSafe(move || drop(payload))).is_err() {
+        std::process::abort();
+    }
+
+    match panic_policy() {
+        PanicPolicy::Abort => std::process::abort(),
+        policy => Some(policy),
+    }
+}
+
+/// Chains a panic hook recording the location of panics raised under [`catch`], which the
+/// unwinding payload does not carry. The previous hook still handles every other panic.
+fn install_hook() {
+    HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if GUARDS.get() == 0 {
+                return previous(info);
+            }
+            LOCATION.set(info.location().map(ToString::to_string));
+        }));
+    });
+}
+
+fn report(report: &PanicReport) {
+    let sink = SINK.read().unwrap_or_else(|err| err.into_inner());
+    let reported = panic::catch_unwind(AssertUnwindSafe(|| match &*sink {
+        Some(sink) => sink(report),
+        None if logger::is_installed() => log::error!("{report}"),
+        None => {
+            let _ = std::writeln!(std::io::stderr(), "{report}");
+        }
+    }));
+    if reported.is_err() {
+        std::process::abort();
+    }
+}
+
+fn payload_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        string::ToString,
+        sync::{Arc, Mutex},
+        thread,
+        vec::Vec,
+    };
+
+    use jni::JNIEnv;
+
+    use super::{
+        PanicPolicy, PanicReport, PanicSink, SINK, panic_policy, set_panic_policy, set_panic_sink,
+    };
+    use crate::{
+        ZygiskModule,
+        api::{V5, ZygiskApi, v1::ZygiskOption},
+        raw::ZygiskRaw,
+        testing::{AppFixture, Call, MockHost},
+    };
+
+    #[derive(Default)]
+    struct Panicking;
+
+    impl ZygiskModule for Panicking {
+        type Api = V5;
+        type State = ();
+
+        fn pre_app_specialize<'a>(
+            &self,
+            _: ZygiskApi<'a, V5>,
+            _: JNIEnv<'a>,
+            _: &'a mut <V5 as ZygiskRaw<'a>>::AppSpecializeArgs,
+            _: &mut (),
+        ) {
+            panic!("no config for {}", "com.example");
+        }
+    }
+
+    #[derive(Default)]
+    struct PanicsOnLoad;
+
+    impl ZygiskModule for PanicsOnLoad {
+        type Api = V5;
+        type State = ();
+
+        fn on_load(&self, _: ZygiskApi<'_, V5>, _: JNIEnv<'_>) {
+            panic!("missing config");
+        }
+    }
+
+    /// Puts back the panic policy and sink replaced by a test, even if it fails
+    struct RestoreGlobals {
+        policy: PanicPolicy,
+        sink: Option<PanicSink>,
+    }
+
+    impl RestoreGlobals {
+        fn replace(
+            policy: PanicPolicy,
+            sink: impl Fn(&PanicReport) + Send + Sync + 'static,
+        ) -> Self {
+            let previous = Self {
+                policy: panic_policy(),
+                sink: SINK.write().unwrap_or_else(|err| err.into_inner()).take(),
+            };
+            set_panic_policy(policy);
+            set_panic_sink(sink);
+            previous
+        }
+    }
+
+    impl Drop for RestoreGlobals {
+        fn drop(&mut self) {
+            set_panic_policy(self.policy);
+            *SINK.write().unwrap_or_else(|err| err.into_inner()) = self.sink.take();
+        }
+    }
+
+    #[test]
+    fn reports_callback_panics() {
+        let reports = Arc::new(Mutex::new(Vec::<PanicReport>::new()));
+        let sink = Arc::clone(&reports);
+        // Reports are made on the panicking thread, which tells this test's apart from others'
+        let test_thread = thread::current().id();
+        let _restore = RestoreGlobals::replace(PanicPolicy::ContinueAndUnload, move |report| {
+            if thread::current().id() == test_thread {
+                sink.lock().unwrap().push(report.clone());
+            }
+        });
+
+        let mut fixture = AppFixture::<V5>::new("com.example");
+        assert!(fixture.host.load(Panicking));
+        fixture.specialize();
+
+        assert_eq!(
+            fixture.host.calls(),
+            [
+                Call::RegisterModule { api_version: 5 },
+                Call::SetOption(ZygiskOption::DlCloseModuleLibrary),
+            ]
+        );
+        drop(fixture);
+
+        let mut host = MockHost::<V5>::new();
+        assert!(host.load(PanicsOnLoad));
+        assert_eq!(
+            host.calls(),
+            [
+                Call::RegisterModule { api_version: 5 },
+                Call::SetOption(ZygiskOption::DlCloseModuleLibrary),
+            ]
+        );
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[1].callback, "on_load");
+        assert_eq!(reports[1].message, "missing config");
+        assert_eq!(reports[0].callback, "pre_app_specialize");
+        assert_eq!(reports[0].message, "no config for com.example");
+        assert!(
+            reports[0]
+                .location
+                .as_ref()
+                .unwrap()
+                .starts_with("src/panics.rs:")
+        );
+        assert!(
+            reports[0]
+                .to_string()
+                .starts_with("module panicked in pre_app_specialize at ")
+        );
+    }
+}
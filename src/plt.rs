@@ -0,0 +1,747 @@
+//! Version-independent PLT hook registration.
+//!
+//! API v1 to v3 select the ELFs to hook with a path regex, and allow excluding ELFs from hooks.
+//! From v4 onwards, ELFs are selected by their device and inode numbers, and exclusions are gone.
+//! [`PltHooks`] accepts any kind of [`PltTarget`] and translates it to what the API version in
+//! use understands:
+//!
+//! - On v1 to v3, paths are turned into anchored regexes, and device/inode pairs are resolved to
+//!   the paths they are mapped from.
+//! - On v4 onwards, regexes and paths are resolved against the current memory map into
+//!   device/inode pairs, and exclusions are applied before registering the hooks.
+//!
+//! Regexes are matched by Zygisk (POSIX basic regular expressions) on v1 to v3, and by
+//! [`regex_lite`] on v4 onwards. Patterns restricted to literals, `.`, `*`, `^`, `$` and bracket
+//! expressions behave the same on every version.
+//!
+//! ```no_run
+//! use zygisk_api::{
+//!     api::{V5, ZygiskApi},
+//!     plt::{PltHooks, PltTarget},
+//! };
+//!
+//! fn hook_open(api: &mut ZygiskApi<'_, V5>, replacement: *const (), original: &'static mut *const ()) {
+//!     let mut hooks = PltHooks::new();
+//!     hooks.register(PltTarget::Regex(r"\.so$"), c"open", replacement, original);
+//!     hooks.exclude(PltTarget::Regex(r"/libc\.so$"), c"open");
+//!
+//!     unsafe { hooks.commit(api) }.unwrap();
+//! }
+//! ```
+
+use core::{
+    ffi::CStr,
+    marker::PhantomData,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicPtr, Ordering},
+};
+use std::{
+    ffi::CString,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use libc::{dev_t, ino_t};
+
+use crate::{
+    api::{HasInodePltHooks, HasRegexPltHooks, V1, V2, V3, V4, V5, ZygiskApi},
+    error::ZygiskError,
+    impl_sealing::Sealed,
+    maps::{MapEntry, MemoryMap},
+    utils::impl_fn_ptr,
+};
+
+/// Function pointers with the C calling convention, which PLT hooks can be typed after
+///
+/// Implemented for `extern "C" fn` and `unsafe extern "C" fn` pointers of up to twelve arguments,
+/// including variadic ones.
+pub trait FnPtr: Copy + Sealed {
+    #[doc(hidden)]
+    fn into_ptr(self) -> *const ();
+
+    #[doc(hidden)]
+    unsafe fn from_ptr(ptr: *const ()) -> Self;
+}
+
+impl_fn_ptr!(FnPtr, "C", variadic);
+
+/// The ELFs loaded in memory that a PLT hook applies to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PltTarget<'a> {
+    /// ELFs whose path matches a regex
+    Regex(&'a str),
+    /// The ELF mapped from exactly this path
+    Path(&'a str),
+    /// The ELF identified by a device and inode pair
+    Inode { device: dev_t, inode: ino_t },
+}
+
+#[derive(Clone, Debug)]
+enum OwnedTarget {
+    Regex(String),
+    Path(String),
+    Inode { device: dev_t, inode: ino_t },
+}
+
+impl From<PltTarget<'_>> for OwnedTarget {
+    fn from(target: PltTarget<'_>) -> Self {
+        match target {
+            PltTarget::Regex(regex) => Self::Regex(regex.to_string()),
+            PltTarget::Path(path) => Self::Path(path.to_string()),
+            PltTarget::Inode { device, inode } => Self::Inode { device, inode },
+        }
+    }
+}
+
+enum Matcher<'t> {
+    Regex(regex_lite::Regex),
+    Path(&'t str),
+    Inode { device: dev_t, inode: ino_t },
+}
+
+impl OwnedTarget {
+    fn matcher(&self) -> Result<Matcher<'_>, ZygiskError> {
+        Ok(match self {
+            Self::Regex(regex) => Matcher::Regex(
+                regex_lite::Regex::new(regex)
+                    .map_err(|_| ZygiskError::InvalidPltRegex(regex.clone()))?,
+            ),
+            Self::Path(path) => Matcher::Path(path),
+            &Self::Inode { device, inode } => Matcher::Inode { device, inode },
+        })
+    }
+
+    /// Returns the regexes selecting this target, in the dialect of Zygisk (POSIX basic regular expressions).
+    fn regexes(&self, map: &mut Option<MemoryMap>) -> Result<Vec<CString>, ZygiskError> {
+        let paths = match self {
+            Self::Regex(regex) => return Ok(Vec::from([to_cstring(regex.clone())?])),
+            Self::Path(path) => Vec::from([path.clone()]),
+            &Self::Inode { device, inode } => {
+                let map = match map {
+                    Some(map) => map,
+                    None => map.insert(MemoryMap::current()?),
+                };
+
+                let mut paths = Vec::<String>::new();
+                for entry in map.entries() {
+                    if entry.file_id() == Some((device, inode))
+                        && let Some(path) = &entry.path
+                        && !paths.contains(path)
+                    {
+                        paths.push(path.clone());
+                    }
+                }
+                paths
+            }
+        };
+
+        paths
+            .iter()
+            .map(|path| to_cstring(anchored_regex(path)))
+            .collect()
+    }
+}
+
+impl Matcher<'_> {
+    fn matches(&self, entry: &MapEntry) -> bool {
+        match self {
+            Self::Regex(regex) => entry
+                .path
+                .as_deref()
+                .is_some_and(|path| regex.is_match(path)),
+            Self::Path(target) => entry.path.as_deref() == Some(*target),
+            &Self::Inode { device, inode } => entry.file_id() == Some((device, inode)),
+        }
+    }
+}
+
+/// Returns [`ZygiskError::InvalidPltRegex`] if `regex` contains a nul byte, which Zygisk cannot be
+/// passed.
+fn to_cstring(regex: String) -> Result<CString, ZygiskError> {
+    CString::new(regex).map_err(|err| {
+        ZygiskError::InvalidPltRegex(String::from_utf8_lossy(&err.into_vec()).into_owned())
+    })
+}
+
+/// Returns a POSIX basic regular expression matching exactly `path`.
+fn anchored_regex(path: &str) -> String {
+    let mut regex = String::with_capacity(path.len() + 2);
+
+    regex.push('^');
+    for c in path.chars() {
+        if matches!(c, '.' | '[' | ']' | '\\' | '*' | '^' | '$') {
+            regex.push('\\');
+        }
+        regex.push(c);
+    }
+    regex.push('$');
+
+    regex
+}
+
+struct Hook<'a> {
+    target: OwnedTarget,
+    symbol: CString,
+    replacement: *const (),
+    original: NonNull<*const ()>,
+    _original: PhantomData<&'a mut *const ()>,
+}
+
+struct Exclusion {
+    target: OwnedTarget,
+    symbol: CString,
+}
+
+/// A set of PLT hooks, registered and committed together through any API version
+#[derive(Default)]
+pub struct PltHooks<'a> {
+    hooks: Vec<Hook<'a>>,
+    exclusions: Vec<Exclusion>,
+}
+
+impl<'a> PltHooks<'a> {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// For ELFs matching `target`, replace function `symbol` with `replacement`.
+    /// The original function pointer will be saved to `original` when the hooks are committed.
+    pub fn register(
+        &mut self,
+        target: PltTarget<'_>,
+        symbol: &CStr,
+        replacement: *const (),
+        original: &'a mut *const (),
+    ) {
+        self.hooks.push(Hook {
+            target: target.into(),
+            symbol: symbol.into(),
+            replacement,
+            original: original.into(),
+            _original: PhantomData,
+        });
+    }
+
+    /// For ELFs matching `target`, replace function `symbol` with `replacement`.
+    /// The original function will be available from `hook` once the hooks are committed.
+    pub fn register_hook<F: FnPtr>(
+        &mut self,
+        target: PltTarget<'_>,
+        symbol: &CStr,
+        hook: &'a PltHook<F>,
+        replacement: F,
+    ) {
+        self.hooks.push(Hook {
+            target: target.into(),
+            symbol: symbol.into(),
+            replacement: replacement.into_ptr(),
+            original: hook.slot(),
+            _original: PhantomData,
+        });
+    }
+
+    /// For ELFs matching `target`, exclude hooks registered for `symbol`.
+    /// If `symbol` is empty, then all symbols will be excluded.
+    pub fn exclude(&mut self, target: PltTarget<'_>, symbol: &CStr) {
+        self.exclusions.push(Exclusion {
+            target: target.into(),
+            symbol: symbol.into(),
+        });
+    }
+
+    /// Register all the hooks and exclusions with Zygisk, then commit them.
+    ///
+    /// Returns [`ZygiskError::PltHookCommitError`] if Zygisk failed to commit the hooks,
+    /// [`ZygiskError::InvalidPltRegex`] if a regex could not be compiled or contains a nul byte, or
+    /// the error encountered while reading the memory map of the current process. Nothing is
+    /// registered with Zygisk unless every target could be resolved.
+    ///
+    /// # Safety
+    ///
+    /// A badly designed hook or misuse of raw pointers may lead to memory unsafety.
+    #[inline]
+    pub unsafe fn commit(self, api: &mut impl PltBackend) -> Result<(), ZygiskError> {
+        unsafe { api.commit_hooks(self) }
+    }
+
+    unsafe fn commit_by_regex(self, api: &mut impl HasRegexPltHooks) -> Result<(), ZygiskError> {
+        // Resolve every target first, so that nothing is registered if one of them fails
+        let mut map = None;
+        let hooks = self
+            .hooks
+            .iter()
+            .map(|hook| Ok((hook, hook.target.regexes(&mut map)?)))
+            .collect::<Result<Vec<_>, ZygiskError>>()?;
+        let exclusions = self
+            .exclusions
+            .iter()
+            .map(|exclusion| Ok((exclusion, exclusion.target.regexes(&mut map)?)))
+            .collect::<Result<Vec<_>, ZygiskError>>()?;
+
+        for (hook, regexes) in hooks {
+            for regex in regexes {
+                unsafe {
+                    api.plt_hook_register(
+                        &regex,
+                        &hook.symbol,
+                        hook.replacement,
+                        &mut *hook.original.as_ptr(),
+                    )
+                };
+            }
+        }
+
+        for (exclusion, regexes) in exclusions {
+            for regex in regexes {
+                unsafe { api.plt_hook_exclude(&regex, &exclusion.symbol) };
+            }
+        }
+
+        api.plt_hook_commit()
+    }
+
+    unsafe fn commit_by_inode(self, api: &mut impl HasInodePltHooks) -> Result<(), ZygiskError> {
+        // Resolve every target first, so that nothing is registered if one of them fails
+        let hooks = self
+            .hooks
+            .iter()
+            .map(|hook| Ok((hook, hook.target.matcher()?)))
+            .collect::<Result<Vec<_>, ZygiskError>>()?;
+        let exclusions = self
+            .exclusions
+            .iter()
+            .map(|exclusion| Ok((exclusion.target.matcher()?, exclusion.symbol.as_c_str())))
+            .collect::<Result<Vec<_>, ZygiskError>>()?;
+        let map = MemoryMap::current()?;
+
+        for (hook, target) in hooks {
+            let files = map.files_matching(|entry| target.matches(entry));
+            for (device, inode) in files {
+                let excluded = exclusions.iter().any(|(matcher, symbol)| {
+                    (symbol.is_empty() || *symbol == hook.symbol.as_c_str())
+                        && map
+                            .entries()
+                            .iter()
+                            .filter(|entry| entry.file_id() == Some((device, inode)))
+                            .any(|entry| matcher.matches(entry))
+                });
+
+                if !excluded {
+                    unsafe {
+                        api.plt_hook_register(
+                            device,
+                            inode,
+                            &hook.symbol,
+                            hook.replacement,
+                            &mut *hook.original.as_ptr(),
+                        )
+                    };
+                }
+            }
+        }
+
+        api.plt_hook_commit()
+    }
+}
+
+/// A typed PLT hook, holding the original function once the hook is committed
+///
+/// `F` is the type of the hooked function, such as `unsafe extern "C" fn(*const c_char, c_int) -> c_int`.
+/// The replacement is registered with the same type, and [`PltHook::original`] returns the original
+/// function with that type, so the signatures cannot silently diverge. `F` must be a C function
+/// pointer (see [`FnPtr`]):
+///
+/// ```compile_fail
+/// static NOT_A_FUNCTION: zygisk_api::plt::PltHook<usize> = zygisk_api::plt::PltHook::new();
+/// ```
+///
+/// Handles can be created in a `const` context, and are usually kept in a `static`:
+///
+/// ```no_run
+/// use core::ffi::{c_char, c_int};
+///
+/// use zygisk_api::{
+///     api::{V5, ZygiskApi},
+///     maps::MemoryMap,
+///     plt::PltHook,
+/// };
+///
+/// type OpenFn = unsafe extern "C" fn(*const c_char, c_int) -> c_int;
+///
+/// static OPEN: PltHook<OpenFn> = PltHook::new();
+///
+/// unsafe extern "C" fn open(path: *const c_char, flags: c_int) -> c_int {
+///     unsafe { OPEN.original().unwrap()(path, flags) }
+/// }
+///
+/// fn hook_libc(api: &mut ZygiskApi<'_, V5>) {
+///     for (device, inode) in MemoryMap::current().unwrap().files_with_suffix("/libc.so") {
+///         unsafe { OPEN.register_inode(api, device, inode, c"open", open) };
+///     }
+///     api.plt_hook_commit().unwrap();
+/// }
+/// ```
+pub struct PltHook<F> {
+    original: AtomicPtr<()>,
+    _fn: PhantomData<F>,
+}
+
+impl<F: FnPtr> PltHook<F> {
+    /// Create a handle with no original function.
+    pub const fn new() -> Self {
+        Self {
+            original: AtomicPtr::new(ptr::null_mut()),
+            _fn: PhantomData,
+        }
+    }
+
+    /// Returns the original function, once the hook has been committed.
+    ///
+    /// Returns [`ZygiskError::PltHookNotFound`] if no original was written to the handle, meaning
+    /// that the symbol was not found in any of the targeted ELFs (or that the hooks were not committed yet).
+    #[inline]
+    pub fn original(&self) -> Result<F, ZygiskError> {
+        let original = self.original.load(Ordering::Acquire);
+
+        if original.is_null() {
+            Err(ZygiskError::PltHookNotFound)
+        } else {
+            // SAFETY: Zygisk wrote the original function, of type `F`, here.
+            Ok(unsafe { F::from_ptr(original) })
+        }
+    }
+
+    /// For ELFs loaded in memory matching `regex`, replace function `symbol` with `replacement`.
+    ///
+    /// # Safety
+    ///
+    /// See [`HasRegexPltHooks::plt_hook_register`]. The handle must stay valid until the hooks are committed.
+    #[inline]
+    pub unsafe fn register_regex(
+        &self,
+        api: &mut impl HasRegexPltHooks,
+        regex: &CStr,
+        symbol: &CStr,
+        replacement: F,
+    ) {
+        unsafe {
+            api.plt_hook_register(regex, symbol, replacement.into_ptr(), self.slot().as_mut())
+        }
+    }
+
+    /// For ELFs loaded in memory matching the `device` and `inode` pair, replace function `symbol`
+    /// with `replacement`.
+    ///
+    /// # Safety
+    ///
+    /// See [`HasInodePltHooks::plt_hook_register`]. The handle must stay valid until the hooks are committed.
+    #[inline]
+    pub unsafe fn register_inode(
+        &self,
+        api: &mut impl HasInodePltHooks,
+        device: dev_t,
+        inode: ino_t,
+        symbol: &CStr,
+        replacement: F,
+    ) {
+        unsafe {
+            api.plt_hook_register(
+                device,
+                inode,
+                symbol,
+                replacement.into_ptr(),
+                self.slot().as_mut(),
+            )
+        }
+    }
+
+    #[inline]
+    fn slot(&self) -> NonNull<*const ()> {
+        // SAFETY: `AtomicPtr::as_ptr` never returns null.
+        unsafe { NonNull::new_unchecked(self.original.as_ptr().cast()) }
+    }
+}
+
+impl<F: FnPtr> Default for PltHook<F> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F> core::fmt::Debug for PltHook<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PltHook")
+            .field("original", &self.original.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+/// API versions that [`PltHooks`] can be committed through
+pub trait PltBackend: Sealed {
+    #[doc(hidden)]
+    unsafe fn commit_hooks(&mut self, hooks: PltHooks<'_>) -> Result<(), ZygiskError>;
+}
+
+impl PltBackend for ZygiskApi<'_, V1> {
+    #[inline]
+    unsafe fn commit_hooks(&mut self, hooks: PltHooks<'_>) -> Result<(), ZygiskError> {
+        unsafe { hooks.commit_by_regex(self) }
+    }
+}
+
+impl PltBackend for ZygiskApi<'_, V2> {
+    #[inline]
+    unsafe fn commit_hooks(&mut self, hooks: PltHooks<'_>) -> Result<(), ZygiskError> {
+        unsafe { hooks.commit_by_regex(self) }
+    }
+}
+
+impl PltBackend for ZygiskApi<'_, V3> {
+    #[inline]
+    unsafe fn commit_hooks(&mut self, hooks: PltHooks<'_>) -> Result<(), ZygiskError> {
+        unsafe { hooks.commit_by_regex(self) }
+    }
+}
+
+impl PltBackend for ZygiskApi<'_, V4> {
+    #[inline]
+    unsafe fn commit_hooks(&mut self, hooks: PltHooks<'_>) -> Result<(), ZygiskError> {
+        unsafe { hooks.commit_by_inode(self) }
+    }
+}
+
+impl PltBackend for ZygiskApi<'_, V5> {
+    #[inline]
+    unsafe fn commit_hooks(&mut self, hooks: PltHooks<'_>) -> Result<(), ZygiskError> {
+        unsafe { hooks.commit_by_inode(self) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{string::String, vec::Vec};
+
+    use core::{
+        ffi::{c_char, c_int, c_void},
+        sync::atomic::Ordering,
+    };
+
+    use super::{PltHook, PltHooks, PltTarget, anchored_regex};
+    use crate::{
+        api::{V2, V5},
+        error::ZygiskError,
+        maps::MemoryMap,
+        testing::{Call, MockHost},
+    };
+
+    /// Returns the path, device and inode of a file mapped into the test process.
+    fn mapped_file() -> (String, libc::dev_t, libc::ino_t) {
+        let map = MemoryMap::current().unwrap();
+        let entry = map
+            .entries()
+            .iter()
+            .find(|entry| entry.file_id().is_some() && entry.path.is_some())
+            .unwrap();
+
+        (entry.path.clone().unwrap(), entry.device, entry.inode)
+    }
+
+    #[test]
+    fn escapes_paths() {
+        assert_eq!(
+            anchored_regex("/system/lib64/libc++.so"),
+            r"^/system/lib64/libc++\.so$"
+        );
+    }
+
+    #[test]
+    fn translates_to_regexes() {
+        let (path, device, inode) = mapped_file();
+        let (mut a, mut b) = (core::ptr::null(), core::ptr::null());
+
+        let mut hooks = PltHooks::new();
+        hooks.register(PltTarget::Regex(r"\.so$"), c"open", 0x1000 as _, &mut a);
+        hooks.register(
+            PltTarget::Inode { device, inode },
+            c"close",
+            0x2000 as _,
+            &mut b,
+        );
+        hooks.exclude(PltTarget::Path("/system/lib64/libc.so"), c"");
+
+        let host = MockHost::<V2>::new();
+        unsafe { hooks.commit(&mut host.api()) }.unwrap();
+
+        assert_eq!(
+            host.calls(),
+            [
+                Call::PltHookRegister {
+                    regex: r"\.so$".into(),
+                    symbol: "open".into(),
+                },
+                Call::PltHookRegister {
+                    regex: anchored_regex(&path),
+                    symbol: "close".into(),
+                },
+                Call::PltHookExclude {
+                    regex: r"^/system/lib64/libc\.so$".into(),
+                    symbol: "".into(),
+                },
+                Call::PltHookCommit,
+            ]
+        );
+    }
+
+    #[test]
+    fn resolves_to_inodes() {
+        let (path, device, inode) = mapped_file();
+        let (mut a, mut b, mut c) = (core::ptr::null(), core::ptr::null(), core::ptr::null());
+
+        let mut hooks = PltHooks::new();
+        hooks.register(PltTarget::Path(&path), c"open", 0x1000 as _, &mut a);
+        hooks.register(PltTarget::Regex("^/"), c"close", 0x2000 as _, &mut b);
+        hooks.register(
+            PltTarget::Inode { device, inode },
+            c"read",
+            0x3000 as _,
+            &mut c,
+        );
+        hooks.exclude(PltTarget::Inode { device, inode }, c"close");
+
+        let host = MockHost::<V5>::new();
+        unsafe { hooks.commit(&mut host.api()) }.unwrap();
+
+        let calls = host.calls();
+        let registered = |symbol: &str| {
+            calls
+                .iter()
+                .filter_map(|call| match call {
+                    Call::PltHookRegisterInode {
+                        device,
+                        inode,
+                        symbol: s,
+                    } if s == symbol => Some((*device, *inode)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(registered("open"), [(device, inode)]);
+        assert_eq!(registered("read"), [(device, inode)]);
+        assert!(!registered("close").is_empty());
+        assert!(!registered("close").contains(&(device, inode)));
+        assert_eq!(calls.last(), Some(&Call::PltHookCommit));
+    }
+
+    #[test]
+    fn rejects_invalid_regexes() {
+        let mut original = core::ptr::null();
+        let mut hooks = PltHooks::new();
+        hooks.register(PltTarget::Regex("("), c"open", 0x1000 as _, &mut original);
+
+        let host = MockHost::<V5>::new();
+        assert!(matches!(
+            unsafe { hooks.commit(&mut host.api()) },
+            Err(ZygiskError::InvalidPltRegex(regex)) if regex == "("
+        ));
+        assert!(host.calls().is_empty());
+    }
+
+    #[test]
+    fn registers_nothing_on_error() {
+        let (mut a, mut b) = (core::ptr::null(), core::ptr::null());
+        let mut hooks = PltHooks::new();
+        hooks.register(PltTarget::Regex(r"\.so$"), c"open", 0x1000 as _, &mut a);
+        hooks.register(PltTarget::Regex("("), c"close", 0x2000 as _, &mut b);
+
+        let host = MockHost::<V5>::new();
+        assert!(matches!(
+            unsafe { hooks.commit(&mut host.api()) },
+            Err(ZygiskError::InvalidPltRegex(regex)) if regex == "("
+        ));
+        assert!(host.calls().is_empty());
+    }
+
+    #[test]
+    fn rejects_nul_bytes() {
+        let (mut a, mut b) = (core::ptr::null(), core::ptr::null());
+        let mut hooks = PltHooks::new();
+        hooks.register(PltTarget::Regex(r"\.so$"), c"open", 0x1000 as _, &mut a);
+        hooks.register(PltTarget::Path("/lib\0.so"), c"close", 0x2000 as _, &mut b);
+
+        let host = MockHost::<V2>::new();
+        assert!(matches!(
+            unsafe { hooks.commit(&mut host.api()) },
+            Err(ZygiskError::InvalidPltRegex(regex)) if regex == "^/lib\0\\.so$"
+        ));
+        assert!(host.calls().is_empty());
+    }
+
+    type CloseFn = unsafe extern "C" fn(c_int) -> c_int;
+
+    unsafe extern "C" fn fake_close(_: c_int) -> c_int {
+        42
+    }
+
+    unsafe extern "C" fn replacement_close(_: c_int) -> c_int {
+        -1
+    }
+
+    #[test]
+    fn types_originals() {
+        static CLOSE: PltHook<CloseFn> = PltHook::new();
+        static MISSING: PltHook<CloseFn> = PltHook::new();
+
+        let (_, device, inode) = mapped_file();
+        let mut host = MockHost::<V5>::new();
+        host.set_plt_original("close", fake_close as *const c_void);
+
+        let mut api = host.api();
+        unsafe {
+            CLOSE.register_inode(&mut api, device, inode, c"close", replacement_close);
+            MISSING.register_inode(&mut api, device, inode, c"missing", replacement_close);
+        }
+        assert!(matches!(
+            CLOSE.original(),
+            Err(ZygiskError::PltHookNotFound)
+        ));
+
+        api.plt_hook_commit().unwrap();
+
+        assert_eq!(unsafe { CLOSE.original().unwrap()(0) }, 42);
+        assert!(matches!(
+            MISSING.original(),
+            Err(ZygiskError::PltHookNotFound)
+        ));
+    }
+
+    #[test]
+    fn types_variadic_originals() {
+        type OpenFn = unsafe extern "C" fn(*const c_char, c_int, ...) -> c_int;
+        static OPEN: PltHook<OpenFn> = PltHook::new();
+
+        let original: OpenFn = libc::open;
+        OPEN.original.store(original as *mut (), Ordering::Release);
+        assert_eq!(OPEN.original().unwrap() as *const (), original as *const ());
+    }
+
+    #[test]
+    fn commits_typed_hooks() {
+        let hook = PltHook::<CloseFn>::new();
+        let mut hooks = PltHooks::new();
+        hooks.register_hook(
+            PltTarget::Regex(r"\.so$"),
+            c"close",
+            &hook,
+            replacement_close,
+        );
+
+        let mut host = MockHost::<V2>::new();
+        host.set_plt_original("close", fake_close as *const c_void);
+        unsafe { hooks.commit(&mut host.api()) }.unwrap();
+
+        assert_eq!(unsafe { hook.original().unwrap()(0) }, 42);
+    }
+}
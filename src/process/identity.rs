@@ -0,0 +1,159 @@
+use super::UserId;
+
+/// The storage class of an app data directory
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataStorage {
+    /// Credential encrypted: available once the user has unlocked the device
+    CredentialEncrypted,
+    /// Device encrypted: available right after boot (`android:directBootAware` apps)
+    DeviceEncrypted,
+}
+
+/// The package and process identity of an app process, derived from the `nice_name` and
+/// `app_data_dir` specialization arguments
+///
+/// The package name is taken from the data directory whenever there is one, as the process name
+/// of a multi-process app or of an app sharing its uid does not need to start with it.
+///
+/// ```
+/// use zygisk_api::process::{DataStorage, ProcessIdentity, UserId};
+///
+/// let identity = ProcessIdentity::new("com.example:remote", "/data/user/10/com.example");
+/// assert_eq!(identity.package_name, "com.example");
+/// assert_eq!(identity.process_suffix, Some(":remote"));
+/// assert_eq!(identity.user_id, Some(UserId(10)));
+/// assert_eq!(identity.storage, Some(DataStorage::CredentialEncrypted));
+/// assert!(identity.belongs_to("com.example") && !identity.is_main_process());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProcessIdentity<'a> {
+    /// The full process name
+    pub process_name: &'a str,
+    pub package_name: &'a str,
+    /// The part of the process name after the package name, such as `:remote` or `_zygote`, if it
+    /// follows the package name with a separator
+    pub process_suffix: Option<&'a str>,
+    /// The user owning the data directory, or [`None`] without a (recognized) data directory
+    pub user_id: Option<UserId>,
+    pub storage: Option<DataStorage>,
+}
+
+impl<'a> ProcessIdentity<'a> {
+    /// Derives the identity of a process from its name and data directory, either of which may be
+    /// empty (as decoded from `null`).
+    pub fn new(nice_name: &'a str, app_data_dir: &'a str) -> Self {
+        let data_dir = parse_data_dir(app_data_dir);
+
+        let (package_name, process_suffix) = match data_dir {
+            Some((_, _, package)) => (
+                package,
+                nice_name.strip_prefix(package).filter(|suffix| {
+                    suffix.is_empty()
+                        || suffix.starts_with([':', '.'])
+                        || suffix.starts_with("_zygote")
+                }),
+            ),
+            None => match nice_name.find(':') {
+                Some(colon) => (&nice_name[..colon], Some(&nice_name[colon..])),
+                None => (
+                    nice_name.strip_suffix("_zygote").unwrap_or(nice_name),
+                    nice_name.ends_with("_zygote").then_some("_zygote"),
+                ),
+            },
+        };
+
+        Self {
+            process_name: nice_name,
+            package_name,
+            process_suffix: process_suffix.filter(|suffix| !suffix.is_empty()),
+            user_id: data_dir.map(|(_, user_id, _)| user_id),
+            storage: data_dir.map(|(storage, _, _)| storage),
+        }
+    }
+
+    #[inline]
+    pub fn belongs_to(&self, package_name: &str) -> bool {
+        self.package_name == package_name
+    }
+
+    /// Returns whether this is the default process of the package, named after it.
+    #[inline]
+    pub fn is_main_process(&self) -> bool {
+        self.process_name == self.package_name
+    }
+}
+
+/// Parses `/data/data/<package>`, `/data/user{,_de}/<user>/<package>`, and the same under
+/// `/mnt/expand/<volume>` for apps on adopted storage.
+fn parse_data_dir(dir: &str) -> Option<(DataStorage, UserId, &str)> {
+    let rest = match dir.strip_prefix("/mnt/expand/") {
+        Some(volume) => volume.split_once('/')?.1,
+        None => dir.strip_prefix("/data/")?,
+    };
+
+    let (storage, user_id, package) = match rest.strip_prefix("data/") {
+        Some(package) => (DataStorage::CredentialEncrypted, UserId::SYSTEM, package),
+        None => {
+            let (class, rest) = rest.split_once('/')?;
+            let (user_id, package) = rest.split_once('/')?;
+            let storage = match class {
+                "user" => DataStorage::CredentialEncrypted,
+                "user_de" => DataStorage::DeviceEncrypted,
+                _ => return None,
+            };
+            (storage, UserId(user_id.parse().ok()?), package)
+        }
+    };
+
+    (!package.is_empty() && !package.contains('/')).then_some((storage, user_id, package))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DataStorage, ProcessIdentity};
+    use crate::process::UserId;
+
+    #[test]
+    fn derives_identities() {
+        let identity = ProcessIdentity::new("com.example", "/data/data/com.example");
+        assert_eq!(identity.user_id, Some(UserId::SYSTEM));
+        assert!(identity.is_main_process() && identity.process_suffix.is_none());
+
+        let identity = ProcessIdentity::new(
+            "com.example:push",
+            "/mnt/expand/0a1b2c3d/user_de/10/com.example",
+        );
+        assert_eq!(identity.package_name, "com.example");
+        assert_eq!(identity.process_suffix, Some(":push"));
+        assert_eq!(identity.user_id, Some(UserId(10)));
+        assert_eq!(identity.storage, Some(DataStorage::DeviceEncrypted));
+
+        // A process of a shared uid app, not named after its package
+        let identity = ProcessIdentity::new("com.android.phone", "/data/user/0/com.android.stk");
+        assert!(identity.belongs_to("com.android.stk"));
+        assert_eq!(identity.process_suffix, None);
+
+        // App zygotes and isolated processes have no data directory
+        let identity = ProcessIdentity::new("com.example_zygote", "");
+        assert_eq!(identity.package_name, "com.example");
+        assert_eq!(identity.process_suffix, Some("_zygote"));
+        assert_eq!(identity.user_id, None);
+
+        let identity = ProcessIdentity::new("com.example:isolated", "/data/misc/foo");
+        assert_eq!(identity.package_name, "com.example");
+        assert_eq!(identity.storage, None);
+    }
+
+    #[test]
+    fn ignores_names_extending_the_package() {
+        for nice_name in ["com.example2", "com.examplefoo"] {
+            let identity = ProcessIdentity::new(nice_name, "/data/user/0/com.example");
+            assert_eq!(identity.package_name, "com.example");
+            assert_eq!(identity.process_suffix, None);
+            assert!(!identity.is_main_process());
+        }
+
+        let identity = ProcessIdentity::new("com.example.sandbox", "/data/user/0/com.example");
+        assert_eq!(identity.process_suffix, Some(".sandbox"));
+    }
+}
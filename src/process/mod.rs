@@ -0,0 +1,28 @@
+//! Interpretation of the identity of the process being specialized.
+//!
+//! The specialization arguments identify the app only indirectly, through its uid and a few
+//! strings. The types in this module decode them without further JNI calls:
+//!
+//! ```
+//! use zygisk_api::process::{ProcessIdentity, SeInfo, Uid};
+//!
+//! // An app of a work profile
+//! let uid = Uid::from(1010123);
+//! assert_eq!(uid.user_id().0, 10);
+//! assert!(uid.app_id().is_application());
+//!
+//! let se_info = SeInfo::parse("default:targetSdkVersion=34:complete");
+//! assert_eq!(se_info.target_sdk_version, Some(34));
+//!
+//! let identity = ProcessIdentity::new("com.example:remote", "/data/user/10/com.example");
+//! assert!(identity.belongs_to("com.example"));
+//! ```
+
+mod identity;
+pub use identity::*;
+
+mod se_info;
+pub use se_info::*;
+
+mod uid;
+pub use uid::*;
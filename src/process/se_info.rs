@@ -0,0 +1,81 @@
+use std::vec::Vec;
+
+/// The parsed `se_info` specialization argument
+///
+/// The system server builds `se_info` from the seinfo tag assigned by `mac_permissions.xml`,
+/// followed by colon-separated markers, such as `default:privapp:targetSdkVersion=34:complete`.
+/// SELinux uses it to pick the domain of the app.
+///
+/// ```
+/// use zygisk_api::process::SeInfo;
+///
+/// let se_info = SeInfo::parse("platform:privapp:targetSdkVersion=34:partner:complete");
+/// assert_eq!(se_info.tag, "platform");
+/// assert!(se_info.is_privileged);
+/// assert_eq!(se_info.target_sdk_version, Some(34));
+/// assert_eq!(se_info.categories, ["partner", "complete"]);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SeInfo<'a> {
+    /// The seinfo tag, such as `default`, `platform` or `media`
+    pub tag: &'a str,
+    /// The app is a privileged app (`privapp`)
+    pub is_privileged: bool,
+    /// The app is an instant app (`ephemeralapp`)
+    pub is_ephemeral: bool,
+    /// The value of `targetSdkVersion=`, if present and valid
+    pub target_sdk_version: Option<u32>,
+    /// Every other marker, in order
+    pub categories: Vec<&'a str>,
+}
+
+impl<'a> SeInfo<'a> {
+    /// Parses `se_info`. Markers that are not recognized, including a malformed `targetSdkVersion`,
+    /// end up in [`categories`](SeInfo::categories).
+    pub fn parse(se_info: &'a str) -> Self {
+        let mut markers = se_info.split(':');
+        let mut parsed = Self {
+            tag: markers.next().unwrap_or_default(),
+            ..Self::default()
+        };
+
+        for marker in markers {
+            match marker {
+                "privapp" => parsed.is_privileged = true,
+                "ephemeralapp" => parsed.is_ephemeral = true,
+                _ => {
+                    if let Some(version) = marker.strip_prefix("targetSdkVersion=")
+                        && let Ok(version) = version.parse()
+                    {
+                        parsed.target_sdk_version = Some(version);
+                    } else if !marker.is_empty() {
+                        parsed.categories.push(marker);
+                    }
+                }
+            }
+        }
+
+        parsed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SeInfo;
+
+    #[test]
+    fn parses_markers() {
+        assert_eq!(
+            SeInfo::parse("default:ephemeralapp:targetSdkVersion=abc:complete"),
+            SeInfo {
+                tag: "default",
+                is_privileged: false,
+                is_ephemeral: true,
+                target_sdk_version: None,
+                categories: ["targetSdkVersion=abc", "complete"].into(),
+            }
+        );
+        assert_eq!(SeInfo::parse("media").tag, "media");
+        assert_eq!(SeInfo::parse(""), SeInfo::default());
+    }
+}
@@ -0,0 +1,156 @@
+use core::ops::RangeInclusive;
+
+use jni::sys::jint;
+
+/// A Linux uid, as assigned by Android to the processes of a user's app
+///
+/// Android splits uids into ranges of [`Uid::PER_USER_RANGE`] per user. Within a range, the
+/// [`AppId`] identifies the app, or a special kind of process such as an isolated service.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Uid(pub u32);
+
+/// The index of an Android user: 0 for the owner, other values for secondary users and profiles
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UserId(pub u32);
+
+/// The uid of an app, independent of the user it runs for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AppId(pub u32);
+
+impl Uid {
+    pub const PER_USER_RANGE: u32 = 100000;
+
+    pub const ROOT: Uid = Uid(0);
+    pub const SYSTEM: Uid = Uid(1000);
+    pub const SHELL: Uid = Uid(2000);
+
+    /// Returns the uid of `app_id` when running for `user_id`.
+    #[inline]
+    pub const fn of(user_id: UserId, app_id: AppId) -> Self {
+        Self(user_id.0 * Self::PER_USER_RANGE + app_id.0)
+    }
+
+    #[inline]
+    pub const fn user_id(self) -> UserId {
+        UserId(self.0 / Self::PER_USER_RANGE)
+    }
+
+    #[inline]
+    pub const fn app_id(self) -> AppId {
+        AppId(self.0 % Self::PER_USER_RANGE)
+    }
+}
+
+impl From<jint> for Uid {
+    #[inline]
+    fn from(uid: jint) -> Self {
+        Self(uid as u32)
+    }
+}
+
+impl From<Uid> for jint {
+    #[inline]
+    fn from(uid: Uid) -> Self {
+        uid.0 as jint
+    }
+}
+
+impl UserId {
+    /// The user that owns the device, and runs the system
+    pub const SYSTEM: UserId = UserId(0);
+}
+
+impl AppId {
+    /// Regular apps
+    pub const APPLICATIONS: RangeInclusive<u32> = 10000..=19999;
+    /// SDK runtime sandboxes, one per app (Android 13+)
+    pub const SDK_SANDBOXES: RangeInclusive<u32> = 20000..=29999;
+    /// Gids shared by all users' instances of an app, one per app
+    pub const SHARED_GIDS: RangeInclusive<u32> = 50000..=59999;
+    /// Isolated processes spawned from an app zygote
+    pub const APP_ZYGOTE_ISOLATED: RangeInclusive<u32> = 90000..=98999;
+    /// Isolated services
+    pub const ISOLATED: RangeInclusive<u32> = 99000..=99999;
+
+    /// Returns whether this is the id of a system component rather than of an app.
+    #[inline]
+    pub const fn is_system(self) -> bool {
+        self.0 < *Self::APPLICATIONS.start()
+    }
+
+    #[inline]
+    pub fn is_application(self) -> bool {
+        Self::APPLICATIONS.contains(&self.0)
+    }
+
+    #[inline]
+    pub fn is_sdk_sandbox(self) -> bool {
+        Self::SDK_SANDBOXES.contains(&self.0)
+    }
+
+    #[inline]
+    pub fn is_shared_gid(self) -> bool {
+        Self::SHARED_GIDS.contains(&self.0)
+    }
+
+    /// Returns whether this is the id of an isolated process, including those spawned from an app
+    /// zygote.
+    #[inline]
+    pub fn is_isolated(self) -> bool {
+        self.is_app_zygote_isolated() || Self::ISOLATED.contains(&self.0)
+    }
+
+    #[inline]
+    pub fn is_app_zygote_isolated(self) -> bool {
+        Self::APP_ZYGOTE_ISOLATED.contains(&self.0)
+    }
+
+    /// Returns the id of the SDK sandbox of this app, or [`None`] if this is not an app.
+    #[inline]
+    pub fn sdk_sandbox(self) -> Option<AppId> {
+        self.is_application()
+            .then(|| AppId(self.0 - Self::APPLICATIONS.start() + Self::SDK_SANDBOXES.start()))
+    }
+
+    /// Returns the app that this SDK sandbox belongs to, or [`None`] if this is not a sandbox.
+    #[inline]
+    pub fn sandboxed_app(self) -> Option<AppId> {
+        self.is_sdk_sandbox()
+            .then(|| AppId(self.0 - Self::SDK_SANDBOXES.start() + Self::APPLICATIONS.start()))
+    }
+
+    /// Returns the shared gid of this app, or [`None`] if this is not an app.
+    #[inline]
+    pub fn shared_gid(self) -> Option<AppId> {
+        self.is_application()
+            .then(|| AppId(self.0 - Self::APPLICATIONS.start() + Self::SHARED_GIDS.start()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AppId, Uid, UserId};
+
+    #[test]
+    fn classifies_uids() {
+        let work_app = Uid::from(1010123);
+        assert_eq!(work_app.user_id(), UserId(10));
+        assert_eq!(work_app.app_id(), AppId(10123));
+        assert_eq!(Uid::of(UserId(10), AppId(10123)), work_app);
+        assert!(work_app.app_id().is_application() && !work_app.app_id().is_isolated());
+        assert_eq!(work_app.app_id().shared_gid(), Some(AppId(50123)));
+        assert_eq!(work_app.app_id().sdk_sandbox(), Some(AppId(20123)));
+
+        let sandbox = Uid::from(20123).app_id();
+        assert!(sandbox.is_sdk_sandbox());
+        assert_eq!(sandbox.sandboxed_app(), Some(AppId(10123)));
+
+        let isolated = Uid::from(1099042).app_id();
+        assert!(isolated.is_isolated() && !isolated.is_app_zygote_isolated());
+        let app_zygote = Uid::from(90042).app_id();
+        assert!(app_zygote.is_isolated() && app_zygote.is_app_zygote_isolated());
+
+        assert!(Uid::SYSTEM.app_id().is_system());
+        assert_eq!(Uid::SYSTEM.app_id().shared_gid(), None);
+    }
+}
@@ -1,9 +1,17 @@
-use core::{marker::PhantomData, ptr::NonNull};
+use core::{cell::Cell, marker::PhantomData, ptr::NonNull};
 
 use jni::JNIEnv;
 use libc::c_long;
 
-use crate::{ZygiskModule, impl_sealing::Sealed};
+use crate::{
+    ZygiskModule,
+    api::{HasOptions, ZygiskApi, v1::ZygiskOption, v2::StateFlags},
+    args::{AnyAppSpecializeArgs, AnyServerSpecializeArgs, decode_string},
+    impl_sealing::Sealed,
+    logger::{self, Phase},
+    panics::{self, PanicPolicy},
+    targeting::Targets,
+};
 
 pub mod v1;
 pub mod v2;
@@ -17,11 +25,204 @@ where
     Version: ZygiskRaw<'a> + 'a + ?Sized,
 {
     #[doc(hidden)]
-    pub dispatch: &'a (dyn ZygiskModule<Api = Version> + 'a),
+    pub dispatch: &'a (dyn ModuleDispatch<Version> + 'a),
     #[doc(hidden)]
     pub api_table: ApiTableRef<'a, Version>,
     #[doc(hidden)]
     pub jni_env: JNIEnv<'a>,
+    /// Set when the app process did not match the module's targets
+    #[doc(hidden)]
+    pub untargeted: bool,
+}
+
+impl<'a, Version> RawModule<'a, Version>
+where
+    Version: for<'b> ZygiskRaw<'b> + 'a,
+    ZygiskApi<'a, Version>: HasOptions,
+{
+    /// Prepares for `pre_app_specialize`: records the process name for the logger, and evaluates
+    /// the module's targets. Returns whether the app specialization callbacks should be called.
+    /// Untargeted processes unload the module library.
+    pub(crate) fn enter_app(
+        &mut self,
+        args: &<Version as ZygiskRaw<'a>>::AppSpecializeArgs,
+        flags: impl FnOnce(&ZygiskApi<'a, Version>) -> StateFlags,
+    ) -> bool {
+        if logger::is_installed() {
+            let mut env = unsafe { self.jni_env.unsafe_clone() };
+            if let Ok(Some(nice_name)) = decode_string(&mut env, args.nice_name()) {
+                logger::set_process_name(nice_name);
+            }
+        }
+
+        let Some(targets) = self.dispatch.targets() else {
+            return true;
+        };
+
+        let mut api = ZygiskApi(unsafe { ApiTableRef::from_raw(self.api_table.0) });
+        let flags = flags(&api);
+        let mut env = unsafe { self.jni_env.unsafe_clone() };
+
+        if !targets.matches_app(&mut env, args, flags) {
+            api.set_option(ZygiskOption::DlCloseModuleLibrary);
+            self.untargeted = true;
+        }
+
+        !self.untargeted
+    }
+
+    /// Runs the module's `on_load` callback once the module has been registered, handling its
+    /// panics like those of the other lifecycle callbacks.
+    #[doc(hidden)]
+    pub fn on_load(&mut self) {
+        self.guard(Phase::OnLoad, |raw| {
+            let api = ZygiskApi(unsafe { ApiTableRef::from_raw(raw.api_table.0) });
+            let env = unsafe { raw.jni_env.unsafe_clone() };
+            raw.dispatch.on_load(api, env);
+        });
+    }
+
+    /// Runs the lifecycle callback of `phase`, handling its panics according to the panic policy.
+    pub(crate) fn guard(&mut self, phase: Phase, callback: impl FnOnce(&mut Self)) {
+        let _phase = logger::enter_phase(phase);
+        if matches!(phase, Phase::PreServerSpecialize) && logger::is_installed() {
+            logger::set_process_name("system_server");
+        }
+
+        if panics::catch(phase.callback(), || callback(self))
+            == Some(PanicPolicy::ContinueAndUnload)
+        {
+            let mut api: ZygiskApi<'a, Version> =
+                ZygiskApi(unsafe { ApiTableRef::from_raw(self.api_table.0) });
+            api.set_option(ZygiskOption::DlCloseModuleLibrary);
+        }
+    }
+}
+
+/// Object-safe view of a [`ZygiskModule`], called by the ABI trampolines
+#[doc(hidden)]
+pub trait ModuleDispatch<Version>
+where
+    Version: for<'a> ZygiskRaw<'a>,
+{
+    fn on_load(&self, api: ZygiskApi<'_, Version>, env: JNIEnv<'_>);
+
+    fn targets(&self) -> Option<&Targets>;
+
+    fn pre_app_specialize<'a>(
+        &self,
+        api: ZygiskApi<'a, Version>,
+        env: JNIEnv<'a>,
+        args: &'a mut <Version as ZygiskRaw<'a>>::AppSpecializeArgs,
+    );
+
+    fn post_app_specialize<'a>(
+        &self,
+        api: ZygiskApi<'a, Version>,
+        env: JNIEnv<'a>,
+        args: &'a <Version as ZygiskRaw<'a>>::AppSpecializeArgs,
+    );
+
+    fn pre_server_specialize<'a>(
+        &self,
+        api: ZygiskApi<'a, Version>,
+        env: JNIEnv<'a>,
+        args: &'a mut <Version as ZygiskRaw<'a>>::ServerSpecializeArgs,
+    );
+
+    fn post_server_specialize<'a>(
+        &self,
+        api: ZygiskApi<'a, Version>,
+        env: JNIEnv<'a>,
+        args: &'a <Version as ZygiskRaw<'a>>::ServerSpecializeArgs,
+    );
+}
+
+/// A module instance, along with the state it carries from a pre-specialization callback to the
+/// matching post-specialization callback
+#[doc(hidden)]
+pub struct Dispatcher<M>
+where
+    M: ZygiskModule,
+{
+    pub module: M,
+    state: Cell<Option<M::State>>,
+}
+
+impl<M> Dispatcher<M>
+where
+    M: ZygiskModule,
+{
+    #[inline(always)]
+    pub const fn new(module: M) -> Self {
+        Self {
+            module,
+            state: Cell::new(None),
+        }
+    }
+}
+
+impl<M> ModuleDispatch<M::Api> for Dispatcher<M>
+where
+    M: ZygiskModule,
+    M::Api: 'static,
+{
+    #[inline(always)]
+    fn on_load(&self, api: ZygiskApi<'_, M::Api>, env: JNIEnv<'_>) {
+        self.module.on_load(api, env);
+    }
+
+    #[inline(always)]
+    fn targets(&self) -> Option<&Targets> {
+        self.module.targets()
+    }
+
+    #[inline(always)]
+    fn pre_app_specialize<'a>(
+        &self,
+        api: ZygiskApi<'a, M::Api>,
+        env: JNIEnv<'a>,
+        args: &'a mut <M::Api as ZygiskRaw<'a>>::AppSpecializeArgs,
+    ) {
+        let mut state = M::State::default();
+        self.module.pre_app_specialize(api, env, args, &mut state);
+        self.state.set(Some(state));
+    }
+
+    #[inline(always)]
+    fn post_app_specialize<'a>(
+        &self,
+        api: ZygiskApi<'a, M::Api>,
+        env: JNIEnv<'a>,
+        args: &'a <M::Api as ZygiskRaw<'a>>::AppSpecializeArgs,
+    ) {
+        let state = self.state.take().unwrap_or_default();
+        self.module.post_app_specialize(api, env, args, state);
+    }
+
+    #[inline(always)]
+    fn pre_server_specialize<'a>(
+        &self,
+        api: ZygiskApi<'a, M::Api>,
+        env: JNIEnv<'a>,
+        args: &'a mut <M::Api as ZygiskRaw<'a>>::ServerSpecializeArgs,
+    ) {
+        let mut state = M::State::default();
+        self.module
+            .pre_server_specialize(api, env, args, &mut state);
+        self.state.set(Some(state));
+    }
+
+    #[inline(always)]
+    fn post_server_specialize<'a>(
+        &self,
+        api: ZygiskApi<'a, M::Api>,
+        env: JNIEnv<'a>,
+        args: &'a <M::Api as ZygiskRaw<'a>>::ServerSpecializeArgs,
+    ) {
+        let state = self.state.take().unwrap_or_default();
+        self.module.post_server_specialize(api, env, args, state);
+    }
 }
 
 #[doc(hidden)]
@@ -110,8 +311,8 @@ where
 {
     const API_VERSION: c_long;
     type ApiTable: 'a;
-    type AppSpecializeArgs: 'a;
-    type ServerSpecializeArgs: 'a;
+    type AppSpecializeArgs: AnyAppSpecializeArgs<'a> + 'a;
+    type ServerSpecializeArgs: AnyServerSpecializeArgs<'a> + 'a;
 
     fn abi_from_module(module: &'a mut RawModule<'a, Self>) -> ModuleAbi<'a, Self>;
 
@@ -3,7 +3,10 @@ use core::ptr::NonNull;
 use jni::{JNIEnv, sys::JNINativeMethod};
 use libc::{c_char, c_int, c_long};
 
-use crate::api::{V1, ZygiskApi};
+use crate::{
+    api::{V1, ZygiskApi},
+    logger::Phase,
+};
 
 use super::{ApiTableRef, BaseApi, Instance, ModuleAbi, ModuleAbiRef, RawModule, ZygiskRaw};
 pub(crate) mod transparent {
@@ -25,20 +28,20 @@ pub(crate) mod transparent {
         pub uid: &'a mut jint,
         pub gid: &'a mut jint,
         pub gids: &'a mut jintArray,
-        pub runtime_flags: &'a jint,
-        pub mount_external: &'a jint,
-        pub se_info: &'a JString<'a>,
-        pub nice_name: &'a JString<'a>,
-        pub instruction_set: &'a JString<'a>,
-        pub app_data_dir: &'a JString<'a>,
+        pub runtime_flags: &'a mut jint,
+        pub mount_external: &'a mut jint,
+        pub se_info: &'a mut JString<'a>,
+        pub nice_name: &'a mut JString<'a>,
+        pub instruction_set: &'a mut JString<'a>,
+        pub app_data_dir: &'a mut JString<'a>,
 
         // Optional arguments. Please check whether the pointer is null before de-referencing
-        pub is_child_zygote: Option<&'a jint>,
-        pub is_top_app: Option<&'a jint>,
-        pub pkg_data_info_list: Option<&'a jobjectArray>,
-        pub whitelisted_data_info_list: Option<&'a jobjectArray>,
-        pub mount_data_dirs: Option<&'a jboolean>,
-        pub mount_storage_dirs: Option<&'a jboolean>,
+        pub is_child_zygote: Option<&'a mut jboolean>,
+        pub is_top_app: Option<&'a mut jboolean>,
+        pub pkg_data_info_list: Option<&'a mut jobjectArray>,
+        pub whitelisted_data_info_list: Option<&'a mut jobjectArray>,
+        pub mount_data_dirs: Option<&'a mut jboolean>,
+        pub mount_storage_dirs: Option<&'a mut jboolean>,
     }
 
     #[repr(C)]
@@ -46,9 +49,9 @@ pub(crate) mod transparent {
         pub uid: &'a mut jint,
         pub gid: &'a mut jint,
         pub gids: &'a mut jintArray,
-        pub runtime_flags: &'a jint,
-        pub permitted_capabilities: &'a jlong,
-        pub effective_capabilities: &'a jlong,
+        pub runtime_flags: &'a mut jint,
+        pub permitted_capabilities: &'a mut jlong,
+        pub effective_capabilities: &'a mut jlong,
     }
 }
 
@@ -82,44 +85,60 @@ impl<'a> ZygiskRaw<'a> for V1 {
             m: &mut RawModule<'a, V1>,
             args: &'a mut transparent::AppSpecializeArgs<'a>,
         ) {
-            m.dispatch.pre_app_specialize(
-                ZygiskApi::<V1>(m.api_table),
-                unsafe { m.jni_env.unsafe_clone() },
-                args,
-            );
+            m.guard(Phase::PreAppSpecialize, |m| {
+                if !m.enter_app(args, |_| crate::api::v2::StateFlags::empty()) {
+                    return;
+                }
+
+                m.dispatch.pre_app_specialize(
+                    ZygiskApi::<V1>(m.api_table),
+                    unsafe { m.jni_env.unsafe_clone() },
+                    args,
+                );
+            });
         }
 
         extern "C" fn post_app_specialize<'a>(
             m: &mut RawModule<'a, V1>,
             args: &'a transparent::AppSpecializeArgs<'a>,
         ) {
-            m.dispatch.post_app_specialize(
-                ZygiskApi::<V1>(m.api_table),
-                unsafe { m.jni_env.unsafe_clone() },
-                args,
-            );
+            m.guard(Phase::PostAppSpecialize, |m| {
+                if m.untargeted {
+                    return;
+                }
+
+                m.dispatch.post_app_specialize(
+                    ZygiskApi::<V1>(m.api_table),
+                    unsafe { m.jni_env.unsafe_clone() },
+                    args,
+                );
+            });
         }
 
         extern "C" fn pre_server_specialize<'a>(
             m: &mut RawModule<'a, V1>,
             args: &'a mut transparent::ServerSpecializeArgs<'a>,
         ) {
-            m.dispatch.pre_server_specialize(
-                ZygiskApi::<V1>(m.api_table),
-                unsafe { m.jni_env.unsafe_clone() },
-                args,
-            );
+            m.guard(Phase::PreServerSpecialize, |m| {
+                m.dispatch.pre_server_specialize(
+                    ZygiskApi::<V1>(m.api_table),
+                    unsafe { m.jni_env.unsafe_clone() },
+                    args,
+                );
+            });
         }
 
         extern "C" fn post_server_specialize<'a>(
             m: &mut RawModule<'a, V1>,
             args: &'a transparent::ServerSpecializeArgs<'a>,
         ) {
-            m.dispatch.post_server_specialize(
-                ZygiskApi::<V1>(m.api_table),
-                unsafe { m.jni_env.unsafe_clone() },
-                args,
-            );
+            m.guard(Phase::PostServerSpecialize, |m| {
+                m.dispatch.post_server_specialize(
+                    ZygiskApi::<V1>(m.api_table),
+                    unsafe { m.jni_env.unsafe_clone() },
+                    args,
+                );
+            });
         }
 
         ModuleAbi {
@@ -5,6 +5,7 @@ use libc::{c_char, c_int, c_long};
 
 use crate::{
     api::{V2, ZygiskApi},
+    logger::Phase,
     raw::RawModule,
 };
 
@@ -15,6 +16,7 @@ pub(crate) mod transparent {
     pub use crate::raw::v1::transparent::{AppSpecializeArgs, ServerSpecializeArgs, ZygiskOption};
 
     bitflags::bitflags! {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
         pub struct StateFlags: u32 {
             const PROCESS_GRANTED_ROOT = (1 << 0);
             const PROCESS_ON_DENYLIST = (1 << 1);
@@ -53,44 +55,65 @@ impl<'a> ZygiskRaw<'a> for V2 {
             m: &mut RawModule<'a, V2>,
             args: &'a mut transparent::AppSpecializeArgs<'a>,
         ) {
-            m.dispatch.pre_app_specialize(
-                ZygiskApi::<V2>(m.api_table),
-                unsafe { m.jni_env.unsafe_clone() },
-                args,
-            );
+            m.guard(Phase::PreAppSpecialize, |m| {
+                // Unknown flags must not hide the known ones from the targets
+                if !m.enter_app(args, |api| {
+                    let api_dispatch = unsafe { api.dispatch() };
+                    let flags = unsafe { (api_dispatch.get_flags_fn)(api_dispatch.base.this) };
+                    transparent::StateFlags::from_bits_truncate(flags)
+                }) {
+                    return;
+                }
+
+                m.dispatch.pre_app_specialize(
+                    ZygiskApi::<V2>(m.api_table),
+                    unsafe { m.jni_env.unsafe_clone() },
+                    args,
+                );
+            });
         }
 
         extern "C" fn post_app_specialize<'a>(
             m: &mut RawModule<'a, V2>,
             args: &'a transparent::AppSpecializeArgs<'a>,
         ) {
-            m.dispatch.post_app_specialize(
-                ZygiskApi::<V2>(m.api_table),
-                unsafe { m.jni_env.unsafe_clone() },
-                args,
-            );
+            m.guard(Phase::PostAppSpecialize, |m| {
+                if m.untargeted {
+                    return;
+                }
+
+                m.dispatch.post_app_specialize(
+                    ZygiskApi::<V2>(m.api_table),
+                    unsafe { m.jni_env.unsafe_clone() },
+                    args,
+                );
+            });
         }
 
         extern "C" fn pre_server_specialize<'a>(
             m: &mut RawModule<'a, V2>,
             args: &'a mut transparent::ServerSpecializeArgs<'a>,
         ) {
-            m.dispatch.pre_server_specialize(
-                ZygiskApi::<V2>(m.api_table),
-                unsafe { m.jni_env.unsafe_clone() },
-                args,
-            );
+            m.guard(Phase::PreServerSpecialize, |m| {
+                m.dispatch.pre_server_specialize(
+                    ZygiskApi::<V2>(m.api_table),
+                    unsafe { m.jni_env.unsafe_clone() },
+                    args,
+                );
+            });
         }
 
         extern "C" fn post_server_specialize<'a>(
             m: &mut RawModule<'a, V2>,
             args: &'a transparent::ServerSpecializeArgs<'a>,
         ) {
-            m.dispatch.post_server_specialize(
-                ZygiskApi::<V2>(m.api_table),
-                unsafe { m.jni_env.unsafe_clone() },
-                args,
-            );
+            m.guard(Phase::PostServerSpecialize, |m| {
+                m.dispatch.post_server_specialize(
+                    ZygiskApi::<V2>(m.api_table),
+                    unsafe { m.jni_env.unsafe_clone() },
+                    args,
+                );
+            });
         }
 
         ModuleAbi {
@@ -3,7 +3,10 @@ use core::ptr::NonNull;
 use jni::{JNIEnv, sys::JNINativeMethod};
 use libc::{c_char, c_int, c_long, dev_t, ino_t};
 
-use crate::api::{V4, ZygiskApi};
+use crate::{
+    api::{V4, ZygiskApi},
+    logger::Phase,
+};
 
 use super::{ApiTableRef, BaseApi, Instance, ModuleAbi, ModuleAbiRef, ZygiskRaw};
 
@@ -46,44 +49,65 @@ impl<'a> ZygiskRaw<'a> for V4 {
             m: &mut super::RawModule<'a, V4>,
             args: &'a mut transparent::AppSpecializeArgs<'a>,
         ) {
-            m.dispatch.pre_app_specialize(
-                ZygiskApi::<V4>(m.api_table),
-                unsafe { m.jni_env.unsafe_clone() },
-                args,
-            );
+            m.guard(Phase::PreAppSpecialize, |m| {
+                // Unknown flags must not hide the known ones from the targets
+                if !m.enter_app(args, |api| {
+                    let api_dispatch = unsafe { api.dispatch() };
+                    let flags = unsafe { (api_dispatch.get_flags_fn)(api_dispatch.base.this) };
+                    transparent::StateFlags::from_bits_truncate(flags)
+                }) {
+                    return;
+                }
+
+                m.dispatch.pre_app_specialize(
+                    ZygiskApi::<V4>(m.api_table),
+                    unsafe { m.jni_env.unsafe_clone() },
+                    args,
+                );
+            });
         }
 
         extern "C" fn post_app_specialize<'a>(
             m: &mut super::RawModule<'a, V4>,
             args: &'a transparent::AppSpecializeArgs<'a>,
         ) {
-            m.dispatch.post_app_specialize(
-                ZygiskApi::<V4>(m.api_table),
-                unsafe { m.jni_env.unsafe_clone() },
-                args,
-            );
+            m.guard(Phase::PostAppSpecialize, |m| {
+                if m.untargeted {
+                    return;
+                }
+
+                m.dispatch.post_app_specialize(
+                    ZygiskApi::<V4>(m.api_table),
+                    unsafe { m.jni_env.unsafe_clone() },
+                    args,
+                );
+            });
         }
 
         extern "C" fn pre_server_specialize<'a>(
             m: &mut super::RawModule<'a, V4>,
             args: &'a mut transparent::ServerSpecializeArgs<'a>,
         ) {
-            m.dispatch.pre_server_specialize(
-                ZygiskApi::<V4>(m.api_table),
-                unsafe { m.jni_env.unsafe_clone() },
-                args,
-            );
+            m.guard(Phase::PreServerSpecialize, |m| {
+                m.dispatch.pre_server_specialize(
+                    ZygiskApi::<V4>(m.api_table),
+                    unsafe { m.jni_env.unsafe_clone() },
+                    args,
+                );
+            });
         }
 
         extern "C" fn post_server_specialize<'a>(
             m: &mut super::RawModule<'a, V4>,
             args: &'a transparent::ServerSpecializeArgs<'a>,
         ) {
-            m.dispatch.post_server_specialize(
-                ZygiskApi::<V4>(m.api_table),
-                unsafe { m.jni_env.unsafe_clone() },
-                args,
-            );
+            m.guard(Phase::PostServerSpecialize, |m| {
+                m.dispatch.post_server_specialize(
+                    ZygiskApi::<V4>(m.api_table),
+                    unsafe { m.jni_env.unsafe_clone() },
+                    args,
+                );
+            });
         }
 
         ModuleAbi {
@@ -3,7 +3,10 @@ use core::ptr::NonNull;
 use jni::{JNIEnv, sys::JNINativeMethod};
 use libc::{c_char, c_int, c_long, dev_t, ino_t};
 
-use crate::api::{V5, ZygiskApi};
+use crate::{
+    api::{V5, ZygiskApi},
+    logger::Phase,
+};
 
 use super::{ApiTableRef, BaseApi, Instance, ModuleAbi, ModuleAbiRef, ZygiskRaw};
 
@@ -22,23 +25,23 @@ pub(crate) mod transparent {
         pub uid: &'a mut jint,
         pub gid: &'a mut jint,
         pub gids: &'a mut jintArray,
-        pub runtime_flags: &'a jint,
-        pub rlimits: &'a jobjectArray,
-        pub mount_external: &'a jint,
-        pub se_info: &'a JString<'a>,
-        pub nice_name: &'a JString<'a>,
-        pub instruction_set: &'a JString<'a>,
-        pub app_data_dir: &'a JString<'a>,
+        pub runtime_flags: &'a mut jint,
+        pub rlimits: &'a mut jobjectArray,
+        pub mount_external: &'a mut jint,
+        pub se_info: &'a mut JString<'a>,
+        pub nice_name: &'a mut JString<'a>,
+        pub instruction_set: &'a mut JString<'a>,
+        pub app_data_dir: &'a mut JString<'a>,
 
         // Optional arguments. Please check whether the pointer is null before de-referencing
-        pub fds_to_ignore: Option<&'a jintArray>,
-        pub is_child_zygote: Option<&'a jint>,
-        pub is_top_app: Option<&'a jint>,
-        pub pkg_data_info_list: Option<&'a jobjectArray>,
-        pub whitelisted_data_info_list: Option<&'a jobjectArray>,
-        pub mount_data_dirs: Option<&'a jboolean>,
-        pub mount_storage_dirs: Option<&'a jboolean>,
-        pub mount_sysprop_overrides: Option<&'a jboolean>,
+        pub fds_to_ignore: Option<&'a mut jintArray>,
+        pub is_child_zygote: Option<&'a mut jboolean>,
+        pub is_top_app: Option<&'a mut jboolean>,
+        pub pkg_data_info_list: Option<&'a mut jobjectArray>,
+        pub whitelisted_data_info_list: Option<&'a mut jobjectArray>,
+        pub mount_data_dirs: Option<&'a mut jboolean>,
+        pub mount_storage_dirs: Option<&'a mut jboolean>,
+        pub mount_sysprop_overrides: Option<&'a mut jboolean>,
     }
 }
 
@@ -75,44 +78,65 @@ impl<'a> ZygiskRaw<'a> for V5 {
             m: &mut super::RawModule<'a, V5>,
             args: &'a mut transparent::AppSpecializeArgs<'a>,
         ) {
-            m.dispatch.pre_app_specialize(
-                ZygiskApi::<V5>(m.api_table),
-                unsafe { m.jni_env.unsafe_clone() },
-                args,
-            );
+            m.guard(Phase::PreAppSpecialize, |m| {
+                // Unknown flags must not hide the known ones from the targets
+                if !m.enter_app(args, |api| {
+                    let api_dispatch = unsafe { api.dispatch() };
+                    let flags = unsafe { (api_dispatch.get_flags_fn)(api_dispatch.base.this) };
+                    transparent::StateFlags::from_bits_truncate(flags)
+                }) {
+                    return;
+                }
+
+                m.dispatch.pre_app_specialize(
+                    ZygiskApi::<V5>(m.api_table),
+                    unsafe { m.jni_env.unsafe_clone() },
+                    args,
+                );
+            });
         }
 
         extern "C" fn post_app_specialize<'a>(
             m: &mut super::RawModule<'a, V5>,
             args: &'a transparent::AppSpecializeArgs<'a>,
         ) {
-            m.dispatch.post_app_specialize(
-                ZygiskApi::<V5>(m.api_table),
-                unsafe { m.jni_env.unsafe_clone() },
-                args,
-            );
+            m.guard(Phase::PostAppSpecialize, |m| {
+                if m.untargeted {
+                    return;
+                }
+
+                m.dispatch.post_app_specialize(
+                    ZygiskApi::<V5>(m.api_table),
+                    unsafe { m.jni_env.unsafe_clone() },
+                    args,
+                );
+            });
         }
 
         extern "C" fn pre_server_specialize<'a>(
             m: &mut super::RawModule<'a, V5>,
             args: &'a mut transparent::ServerSpecializeArgs<'a>,
         ) {
-            m.dispatch.pre_server_specialize(
-                ZygiskApi::<V5>(m.api_table),
-                unsafe { m.jni_env.unsafe_clone() },
-                args,
-            );
+            m.guard(Phase::PreServerSpecialize, |m| {
+                m.dispatch.pre_server_specialize(
+                    ZygiskApi::<V5>(m.api_table),
+                    unsafe { m.jni_env.unsafe_clone() },
+                    args,
+                );
+            });
         }
 
         extern "C" fn post_server_specialize<'a>(
             m: &mut super::RawModule<'a, V5>,
             args: &'a transparent::ServerSpecializeArgs<'a>,
         ) {
-            m.dispatch.post_server_specialize(
-                ZygiskApi::<V5>(m.api_table),
-                unsafe { m.jni_env.unsafe_clone() },
-                args,
-            );
+            m.guard(Phase::PostServerSpecialize, |m| {
+                m.dispatch.post_server_specialize(
+                    ZygiskApi::<V5>(m.api_table),
+                    unsafe { m.jni_env.unsafe_clone() },
+                    args,
+                );
+            });
         }
 
         ModuleAbi {
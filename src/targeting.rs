@@ -0,0 +1,438 @@
+//! Declarative selection of the app processes that a module applies to.
+//!
+//! A module returning [`Targets`] from [`ZygiskModule::targets`](crate::ZygiskModule::targets)
+//! only gets its app specialization callbacks called for matching processes. In every other app
+//! process, the module library is unloaded after specialization through
+//! [`ZygiskOption::DlCloseModuleLibrary`](crate::api::v1::ZygiskOption::DlCloseModuleLibrary),
+//! and neither [`pre_app_specialize`](crate::ZygiskModule::pre_app_specialize) nor
+//! [`post_app_specialize`](crate::ZygiskModule::post_app_specialize) get called.
+//!
+//! ```
+//! use zygisk_api::{
+//!     ZygiskModule,
+//!     api::{V5, v2::StateFlags},
+//!     process::UserId,
+//!     targeting::{Rule, Targets},
+//! };
+//!
+//! struct MyModule {
+//!     targets: Targets,
+//! }
+//!
+//! impl Default for MyModule {
+//!     fn default() -> Self {
+//!         Self {
+//!             targets: Targets::new()
+//!                 .rule(Rule::new().package("com.example.app").user(UserId::SYSTEM))
+//!                 .rule(
+//!                     Rule::new()
+//!                         .process("com.example.*:remote")
+//!                         .without_flags(StateFlags::PROCESS_ON_DENYLIST),
+//!                 ),
+//!         }
+//!     }
+//! }
+//!
+//! impl ZygiskModule for MyModule {
+//!     type Api = V5;
+//!     type State = ();
+//!
+//!     fn targets(&self) -> Option<&Targets> {
+//!         Some(&self.targets)
+//!     }
+//! }
+//! ```
+//!
+//! Server specialization is not affected by targeting.
+
+use core::ops::RangeInclusive;
+use std::{string::String, vec::Vec};
+
+use jni::JNIEnv;
+
+use crate::{
+    api::v2::StateFlags,
+    args::{AnyAppSpecializeArgs, decode_string},
+    process::{ProcessIdentity, Uid, UserId},
+};
+
+/// A set of conditions that a process must all fulfill
+///
+/// A rule without any condition matches every process.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rule {
+    package: Option<String>,
+    process: Option<String>,
+    user: Option<UserId>,
+    uids: Option<RangeInclusive<Uid>>,
+    child_zygote: Option<bool>,
+    top_app: Option<bool>,
+    with_flags: StateFlags,
+    without_flags: StateFlags,
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rule {
+    pub const fn new() -> Self {
+        Self {
+            package: None,
+            process: None,
+            user: None,
+            uids: None,
+            child_zygote: None,
+            top_app: None,
+            with_flags: StateFlags::empty(),
+            without_flags: StateFlags::empty(),
+        }
+    }
+
+    /// The process must belong to `package`, as derived by [`ProcessIdentity`].
+    pub fn package(mut self, package: impl Into<String>) -> Self {
+        self.package = Some(package.into());
+        self
+    }
+
+    /// The process name must match `glob`, in which `*` matches any sequence of characters and `?`
+    /// any single character.
+    pub fn process(mut self, glob: impl Into<String>) -> Self {
+        self.process = Some(glob.into());
+        self
+    }
+
+    /// The process must run for `user`.
+    pub fn user(mut self, user: UserId) -> Self {
+        self.user = Some(user);
+        self
+    }
+
+    /// The uid of the process must be within `uids`.
+    pub fn uids(mut self, uids: RangeInclusive<Uid>) -> Self {
+        self.uids = Some(uids);
+        self
+    }
+
+    /// The process must (or must not) be a child zygote, such as an app zygote.
+    pub fn child_zygote(mut self, child_zygote: bool) -> Self {
+        self.child_zygote = Some(child_zygote);
+        self
+    }
+
+    /// The process must (or must not) be started as the top app.
+    pub fn top_app(mut self, top_app: bool) -> Self {
+        self.top_app = Some(top_app);
+        self
+    }
+
+    /// All of `flags` must be set for the process.
+    ///
+    /// State flags are only available from API v2 onwards, and are all unset on API v1.
+    pub fn with_flags(mut self, flags: StateFlags) -> Self {
+        self.with_flags |= flags;
+        self
+    }
+
+    /// None of `flags` may be set for the process.
+    ///
+    /// See [`Rule::with_flags`].
+    pub fn without_flags(mut self, flags: StateFlags) -> Self {
+        self.without_flags |= flags;
+        self
+    }
+
+    pub fn matches(&self, process: &TargetProcess<'_>) -> bool {
+        self.package
+            .as_ref()
+            .is_none_or(|package| process.identity.belongs_to(package))
+            && self
+                .process
+                .as_ref()
+                .is_none_or(|glob| glob_matches(glob, process.identity.process_name))
+            && self.user.is_none_or(|user| process.uid.user_id() == user)
+            && self
+                .uids
+                .as_ref()
+                .is_none_or(|uids| uids.contains(&process.uid))
+            && self
+                .child_zygote
+                .is_none_or(|child_zygote| process.is_child_zygote == child_zygote)
+            && self
+                .top_app
+                .is_none_or(|top_app| process.is_top_app == top_app)
+            && process.flags.contains(self.with_flags)
+            && !process.flags.intersects(self.without_flags)
+    }
+}
+
+/// The processes that a module applies to: those matching any of its rules
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Targets {
+    rules: Vec<Rule>,
+}
+
+impl Targets {
+    /// Creates an empty set of targets, matching no process.
+    pub const fn new() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn matches(&self, process: &TargetProcess<'_>) -> bool {
+        self.rules.iter().any(|rule| rule.matches(process))
+    }
+
+    /// Decodes the facts about an app process from its specialization arguments, and returns
+    /// whether it is targeted.
+    ///
+    /// `null` arguments are treated as empty strings. If an argument cannot be decoded, the process
+    /// is treated as targeted, so that the module is never unloaded from a process it may apply to.
+    pub fn matches_app<'a>(
+        &self,
+        env: &mut JNIEnv<'_>,
+        args: &impl AnyAppSpecializeArgs<'a>,
+        flags: StateFlags,
+    ) -> bool {
+        let (Ok(nice_name), Ok(app_data_dir)) = (
+            decode_string(env, args.nice_name()),
+            decode_string(env, args.app_data_dir()),
+        ) else {
+            return true;
+        };
+        let (nice_name, app_data_dir) = (
+            nice_name.unwrap_or_default(),
+            app_data_dir.unwrap_or_default(),
+        );
+
+        self.matches(&TargetProcess {
+            identity: ProcessIdentity::new(&nice_name, &app_data_dir),
+            uid: Uid::from(args.uid()),
+            is_child_zygote: args.is_child_zygote().is_some_and(|value| value != 0),
+            is_top_app: args.is_top_app().is_some_and(|value| value != 0),
+            flags,
+        })
+    }
+}
+
+/// The facts about an app process that [`Rule`]s are evaluated against
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TargetProcess<'a> {
+    pub identity: ProcessIdentity<'a>,
+    pub uid: Uid,
+    pub is_child_zygote: bool,
+    pub is_top_app: bool,
+    pub flags: StateFlags,
+}
+
+fn glob_matches(glob: &str, name: &str) -> bool {
+    let (glob, name) = (glob.as_bytes(), name.as_bytes());
+    let (mut g, mut n) = (0, 0);
+    // Position of the last `*` in the glob, and of the name character it was matched up to
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match glob.get(g) {
+            Some(b'*') => {
+                backtrack = Some((g, n));
+                g += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                g += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    // Let the `*` swallow one more character
+                    g = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, n));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    glob[g..].iter().all(|&c| c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use std::{rc::Rc, vec::Vec};
+
+    use super::{Rule, TargetProcess, Targets, glob_matches};
+    use crate::{
+        ZygiskModule,
+        api::{V4, ZygiskApi, v1::ZygiskOption, v2::StateFlags},
+        process::{ProcessIdentity, Uid, UserId},
+        raw::ZygiskRaw,
+        testing::{AppFixture, Call},
+    };
+
+    #[test]
+    fn matches_globs() {
+        assert!(glob_matches("com.example", "com.example"));
+        assert!(glob_matches("com.example*", "com.example:remote"));
+        assert!(glob_matches("com.*.app:*", "com.example.app:push"));
+        assert!(glob_matches("*:remote", ":remote"));
+        assert!(glob_matches("com.exampl?", "com.example"));
+        assert!(!glob_matches("com.example", "com.example:remote"));
+        assert!(!glob_matches("*:remote", "com.example:remote2"));
+    }
+
+    #[test]
+    fn evaluates_rules() {
+        let process = TargetProcess {
+            identity: ProcessIdentity::new("com.example:remote", "/data/user/10/com.example"),
+            uid: Uid::from(1010123),
+            is_child_zygote: false,
+            is_top_app: true,
+            flags: StateFlags::PROCESS_ON_DENYLIST,
+        };
+
+        assert!(Rule::new().matches(&process));
+        assert!(
+            Rule::new()
+                .package("com.example")
+                .user(UserId(10))
+                .top_app(true)
+                .matches(&process)
+        );
+        assert!(
+            !Rule::new()
+                .package("com.example")
+                .without_flags(StateFlags::PROCESS_ON_DENYLIST)
+                .matches(&process)
+        );
+        assert!(!Rule::new().uids(Uid(10000)..=Uid(19999)).matches(&process));
+
+        assert!(!Targets::new().matches(&process));
+        assert!(
+            Targets::new()
+                .rule(Rule::new().package("com.other"))
+                .rule(Rule::new().process("*:remote"))
+                .matches(&process)
+        );
+    }
+
+    struct Targeted {
+        targets: Targets,
+        calls: Rc<Cell<usize>>,
+    }
+
+    impl ZygiskModule for Targeted {
+        type Api = V4;
+        type State = ();
+
+        fn targets(&self) -> Option<&Targets> {
+            Some(&self.targets)
+        }
+
+        fn pre_app_specialize<'a>(
+            &self,
+            _: ZygiskApi<'a, V4>,
+            _: jni::JNIEnv<'a>,
+            _: &'a mut <V4 as ZygiskRaw<'a>>::AppSpecializeArgs,
+            _: &mut (),
+        ) {
+            self.calls.set(self.calls.get() + 1);
+        }
+
+        fn post_app_specialize<'a>(
+            &self,
+            _: ZygiskApi<'a, V4>,
+            _: jni::JNIEnv<'a>,
+            _: &'a <V4 as ZygiskRaw<'a>>::AppSpecializeArgs,
+            _: (),
+        ) {
+            self.calls.set(self.calls.get() + 1);
+        }
+    }
+
+    fn specialize(mut fixture: AppFixture<V4>, flags: StateFlags) -> (usize, Vec<Call>) {
+        fixture.host.set_flags(flags.bits());
+
+        let calls = Rc::new(Cell::new(0));
+        assert!(
+            fixture.host.load(Targeted {
+                targets: Targets::new().rule(
+                    Rule::new()
+                        .package("com.example")
+                        .without_flags(StateFlags::PROCESS_ON_DENYLIST),
+                ),
+                calls: Rc::clone(&calls),
+            })
+        );
+
+        fixture.specialize();
+
+        (calls.get(), fixture.host.calls())
+    }
+
+    #[test]
+    fn unloads_from_untargeted_processes() {
+        let (calls, host_calls) = specialize(AppFixture::new("com.example"), StateFlags::empty());
+        assert_eq!(calls, 2);
+        assert_eq!(
+            host_calls,
+            [Call::RegisterModule { api_version: 4 }, Call::GetFlags]
+        );
+
+        for (nice_name, flags) in [
+            ("com.other", StateFlags::empty()),
+            ("com.example", StateFlags::PROCESS_ON_DENYLIST),
+        ] {
+            let (calls, host_calls) = specialize(AppFixture::new(nice_name), flags);
+            assert_eq!(calls, 0);
+            assert_eq!(
+                host_calls,
+                [
+                    Call::RegisterModule { api_version: 4 },
+                    Call::GetFlags,
+                    Call::SetOption(ZygiskOption::DlCloseModuleLibrary),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_known_flags_next_to_unknown_ones() {
+        let flags = StateFlags::from_bits_retain(StateFlags::PROCESS_ON_DENYLIST.bits() | 1 << 20);
+
+        let (calls, host_calls) = specialize(AppFixture::new("com.example"), flags);
+        assert_eq!(calls, 0);
+        assert_eq!(
+            host_calls,
+            [
+                Call::RegisterModule { api_version: 4 },
+                Call::GetFlags,
+                Call::SetOption(ZygiskOption::DlCloseModuleLibrary),
+            ]
+        );
+    }
+
+    #[test]
+    fn applies_when_arguments_cannot_be_decoded() {
+        let mut fixture = AppFixture::new("com.other");
+        // Not a string, so decoding it fails
+        fixture.args.nice_name = fixture.jvm.new_int_array(&[]);
+
+        let (calls, host_calls) = specialize(fixture, StateFlags::empty());
+        assert_eq!(calls, 2);
+        assert_eq!(
+            host_calls,
+            [Call::RegisterModule { api_version: 4 }, Call::GetFlags]
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use core::ptr;
+
+use jni::{
+    objects::JString,
+    sys::{jboolean, jint, jintArray, jlong, jobjectArray, jstring},
+};
+
+use super::FakeJvm;
+use crate::api::{v1, v3, v5};
+
+/// Backing storage for the specialization arguments of an app process
+///
+/// Zygote passes the arguments as pointers into its own stack frame. [`FakeAppArgs`] plays that
+/// role in tests: it owns the raw values, lends them out through the `AppSpecializeArgs` struct of
+/// any API version, and can be inspected once the module has overwritten them.
+///
+/// Optional arguments are not passed unless set to [`Some`].
+#[derive(Clone, Debug)]
+pub struct FakeAppArgs {
+    pub uid: jint,
+    pub gid: jint,
+    pub gids: jintArray,
+    pub runtime_flags: jint,
+    pub rlimits: jobjectArray,
+    pub mount_external: jint,
+    pub se_info: jstring,
+    pub nice_name: jstring,
+    pub instruction_set: jstring,
+    pub app_data_dir: jstring,
+
+    pub fds_to_ignore: Option<jintArray>,
+    pub is_child_zygote: Option<jboolean>,
+    pub is_top_app: Option<jboolean>,
+    pub pkg_data_info_list: Option<jobjectArray>,
+    pub whitelisted_data_info_list: Option<jobjectArray>,
+    pub mount_data_dirs: Option<jboolean>,
+    pub mount_storage_dirs: Option<jboolean>,
+    pub mount_sysprop_overrides: Option<jboolean>,
+}
+
+impl FakeAppArgs {
+    /// Create the arguments of an app process named `nice_name`, running as `uid`.
+    pub fn new(jvm: &FakeJvm, uid: jint, nice_name: &str) -> Self {
+        Self {
+            uid,
+            gid: uid,
+            gids: jvm.new_int_array(&[]),
+            runtime_flags: 0,
+            rlimits: jvm.new_object_array(&[]),
+            mount_external: 0,
+            se_info: jvm.new_string("default"),
+            nice_name: jvm.new_string(nice_name),
+            instruction_set: jvm.new_string("arm64"),
+            app_data_dir: ptr::null_mut(),
+            fds_to_ignore: None,
+            is_child_zygote: None,
+            is_top_app: None,
+            pkg_data_info_list: None,
+            whitelisted_data_info_list: None,
+            mount_data_dirs: None,
+            mount_storage_dirs: None,
+            mount_sysprop_overrides: None,
+        }
+    }
+
+    /// Lend the arguments out in the layout of API v1 and v2.
+    pub fn v1(&mut self) -> v1::AppSpecializeArgs<'_> {
+        v1::AppSpecializeArgs {
+            uid: &mut self.uid,
+            gid: &mut self.gid,
+            gids: &mut self.gids,
+            runtime_flags: &mut self.runtime_flags,
+            mount_external: &mut self.mount_external,
+            se_info: string(&mut self.se_info),
+            nice_name: string(&mut self.nice_name),
+            instruction_set: string(&mut self.instruction_set),
+            app_data_dir: string(&mut self.app_data_dir),
+            is_child_zygote: self.is_child_zygote.as_mut(),
+            is_top_app: self.is_top_app.as_mut(),
+            pkg_data_info_list: self.pkg_data_info_list.as_mut(),
+            whitelisted_data_info_list: self.whitelisted_data_info_list.as_mut(),
+            mount_data_dirs: self.mount_data_dirs.as_mut(),
+            mount_storage_dirs: self.mount_storage_dirs.as_mut(),
+        }
+    }
+
+    /// Lend the arguments out in the layout of API v3 and v4.
+    pub fn v3(&mut self) -> v3::AppSpecializeArgs<'_> {
+        v3::AppSpecializeArgs {
+            uid: &mut self.uid,
+            gid: &mut self.gid,
+            gids: &mut self.gids,
+            runtime_flags: &mut self.runtime_flags,
+            rlimits: &mut self.rlimits,
+            mount_external: &mut self.mount_external,
+            se_info: string(&mut self.se_info),
+            nice_name: string(&mut self.nice_name),
+            instruction_set: string(&mut self.instruction_set),
+            app_data_dir: string(&mut self.app_data_dir),
+            fds_to_ignore: self.fds_to_ignore.as_mut(),
+            is_child_zygote: self.is_child_zygote.as_mut(),
+            is_top_app: self.is_top_app.as_mut(),
+            pkg_data_info_list: self.pkg_data_info_list.as_mut(),
+            whitelisted_data_info_list: self.whitelisted_data_info_list.as_mut(),
+            mount_data_dirs: self.mount_data_dirs.as_mut(),
+            mount_storage_dirs: self.mount_storage_dirs.as_mut(),
+        }
+    }
+
+    /// Lend the arguments out in the layout of API v5.
+    pub fn v5(&mut self) -> v5::AppSpecializeArgs<'_> {
+        v5::AppSpecializeArgs {
+            uid: &mut self.uid,
+            gid: &mut self.gid,
+            gids: &mut self.gids,
+            runtime_flags: &mut self.runtime_flags,
+            rlimits: &mut self.rlimits,
+            mount_external: &mut self.mount_external,
+            se_info: string(&mut self.se_info),
+            nice_name: string(&mut self.nice_name),
+            instruction_set: string(&mut self.instruction_set),
+            app_data_dir: string(&mut self.app_data_dir),
+            fds_to_ignore: self.fds_to_ignore.as_mut(),
+            is_child_zygote: self.is_child_zygote.as_mut(),
+            is_top_app: self.is_top_app.as_mut(),
+            pkg_data_info_list: self.pkg_data_info_list.as_mut(),
+            whitelisted_data_info_list: self.whitelisted_data_info_list.as_mut(),
+            mount_data_dirs: self.mount_data_dirs.as_mut(),
+            mount_storage_dirs: self.mount_storage_dirs.as_mut(),
+            mount_sysprop_overrides: self.mount_sysprop_overrides.as_mut(),
+        }
+    }
+}
+
+/// Backing storage for the specialization arguments of the system server process
+///
+/// See [`FakeAppArgs`].
+#[derive(Clone, Debug)]
+pub struct FakeServerArgs {
+    pub uid: jint,
+    pub gid: jint,
+    pub gids: jintArray,
+    pub runtime_flags: jint,
+    pub permitted_capabilities: jlong,
+    pub effective_capabilities: jlong,
+}
+
+/// The arguments of the system server with a `null` `gids` array, for tests that do not need a
+/// [`FakeJvm`].
+impl Default for FakeServerArgs {
+    fn default() -> Self {
+        Self {
+            uid: 1000,
+            gid: 1000,
+            gids: ptr::null_mut(),
+            runtime_flags: 0,
+            permitted_capabilities: 0,
+            effective_capabilities: 0,
+        }
+    }
+}
+
+impl FakeServerArgs {
+    pub fn new(jvm: &FakeJvm) -> Self {
+        Self {
+            uid: 1000,
+            gid: 1000,
+            gids: jvm.new_int_array(&[]),
+            runtime_flags: 0,
+            permitted_capabilities: 0,
+            effective_capabilities: 0,
+        }
+    }
+
+    /// Lend the arguments out (in the layout shared by every API version).
+    pub fn args(&mut self) -> v1::ServerSpecializeArgs<'_> {
+        v1::ServerSpecializeArgs {
+            uid: &mut self.uid,
+            gid: &mut self.gid,
+            gids: &mut self.gids,
+            runtime_flags: &mut self.runtime_flags,
+            permitted_capabilities: &mut self.permitted_capabilities,
+            effective_capabilities: &mut self.effective_capabilities,
+        }
+    }
+}
+
+fn string<'a>(string: &'a mut jstring) -> &'a mut JString<'a> {
+    // SAFETY: `JString` is a `repr(transparent)` wrapper around `jstring`.
+    unsafe { &mut *(string as *mut jstring).cast::<JString<'a>>() }
+}
@@ -0,0 +1,262 @@
+use core::{cell::RefCell, ffi::CStr, mem, ptr};
+use std::{boxed::Box, ffi::CString, string::String, vec::Vec};
+
+use jni::{
+    JNIEnv,
+    sys::{
+        self, JNINativeInterface_, jboolean, jint, jintArray, jobject, jobjectArray, jsize, jstring,
+    },
+};
+use libc::c_char;
+
+enum FakeObject {
+    String(CString),
+    IntArray(RefCell<Vec<jint>>),
+    ObjectArray(RefCell<Vec<jobject>>),
+}
+
+#[repr(C)]
+struct FakeEnv {
+    // Must come first: a `JNIEnv` is a pointer to the function table pointer.
+    functions: *const JNINativeInterface_,
+    interface: Box<JNINativeInterface_>,
+    // Boxed, so that object addresses stay stable as the arena grows
+    #[allow(clippy::vec_box)]
+    objects: RefCell<Vec<Box<FakeObject>>>,
+}
+
+impl FakeEnv {
+    fn alloc(&self, object: FakeObject) -> jobject {
+        let mut object = Box::new(object);
+        let ptr = &mut *object as *mut FakeObject as jobject;
+        self.objects.borrow_mut().push(object);
+        ptr
+    }
+
+    fn find<R>(&self, obj: jobject, f: impl FnOnce(&FakeObject) -> R) -> Option<R> {
+        self.objects
+            .borrow()
+            .iter()
+            .find(|object| ptr::eq::<FakeObject>(&***object, obj.cast()))
+            .map(|object| f(object))
+    }
+}
+
+/// A minimal JNI environment backed by Rust values
+///
+/// Only strings, `int[]` and object arrays are supported, through the handful of JNI functions that
+/// this crate calls. Objects live as long as the [`FakeJvm`], and are never garbage-collected.
+/// Calling any other JNI function fails with [`jni::errors::Error::JNIEnvMethodNotFound`].
+///
+/// ```
+/// use zygisk_api::{api::V5, testing::{FakeJvm, MockHost}};
+///
+/// let jvm = FakeJvm::new();
+/// let host = unsafe { MockHost::<V5>::with_jni_env(jvm.env_ptr()) };
+/// ```
+pub struct FakeJvm {
+    env: Box<FakeEnv>,
+}
+
+impl Default for FakeJvm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FakeJvm {
+    pub fn new() -> Self {
+        // SAFETY: every field of the function table is either a raw pointer or an `Option` of a function pointer.
+        let mut interface = Box::new(unsafe { mem::zeroed::<JNINativeInterface_>() });
+        interface.GetStringUTFChars = Some(get_string_utf_chars);
+        interface.ReleaseStringUTFChars = Some(release_string_utf_chars);
+        interface.GetStringUTFLength = Some(get_string_utf_length);
+        interface.NewStringUTF = Some(new_string_utf);
+        interface.GetArrayLength = Some(get_array_length);
+        interface.NewIntArray = Some(new_int_array);
+        interface.GetIntArrayRegion = Some(get_int_array_region);
+        interface.SetIntArrayRegion = Some(set_int_array_region);
+        interface.GetObjectArrayElement = Some(get_object_array_element);
+        interface.SetObjectArrayElement = Some(set_object_array_element);
+        interface.DeleteLocalRef = Some(delete_local_ref);
+        interface.ExceptionCheck = Some(exception_check);
+
+        let mut env = Box::new(FakeEnv {
+            functions: ptr::null(),
+            interface,
+            objects: RefCell::new(Vec::new()),
+        });
+        env.functions = &*env.interface;
+
+        Self { env }
+    }
+
+    /// Returns the raw JNI environment, to be passed to [`MockHost::with_jni_env`](super::MockHost::with_jni_env).
+    pub fn env_ptr(&self) -> *mut sys::JNIEnv {
+        &*self.env as *const FakeEnv as *mut sys::JNIEnv
+    }
+
+    pub fn env(&self) -> JNIEnv<'_> {
+        unsafe { JNIEnv::from_raw(self.env_ptr()).unwrap_unchecked() }
+    }
+
+    pub fn new_string(&self, string: &str) -> jstring {
+        self.env
+            .alloc(FakeObject::String(CString::new(string).unwrap()))
+    }
+
+    pub fn new_int_array(&self, elements: &[jint]) -> jintArray {
+        self.env
+            .alloc(FakeObject::IntArray(RefCell::new(elements.to_vec())))
+    }
+
+    pub fn new_object_array(&self, elements: &[jobject]) -> jobjectArray {
+        self.env
+            .alloc(FakeObject::ObjectArray(RefCell::new(elements.to_vec())))
+    }
+
+    /// Returns the contents of a string created by this JVM, or [`None`] for `null`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `string` is not a string created by this JVM.
+    pub fn string(&self, string: jstring) -> Option<String> {
+        if string.is_null() {
+            return None;
+        }
+        self.env
+            .find(string, |object| match object {
+                FakeObject::String(string) => string.to_string_lossy().into_owned(),
+                _ => panic!("not a string"),
+            })
+            .or_else(|| panic!("not an object of this FakeJvm"))
+    }
+
+    /// Returns the contents of an `int[]` created by this JVM, or [`None`] for `null`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `array` is not an `int[]` created by this JVM.
+    pub fn int_array(&self, array: jintArray) -> Option<Vec<jint>> {
+        if array.is_null() {
+            return None;
+        }
+        self.env
+            .find(array, |object| match object {
+                FakeObject::IntArray(array) => array.borrow().clone(),
+                _ => panic!("not an int[]"),
+            })
+            .or_else(|| panic!("not an object of this FakeJvm"))
+    }
+}
+
+unsafe fn object<'a>(obj: jobject) -> Option<&'a FakeObject> {
+    unsafe { (obj as *const FakeObject).as_ref() }
+}
+
+unsafe fn fake_env<'a>(env: *mut sys::JNIEnv) -> &'a FakeEnv {
+    unsafe { &*(env as *const FakeEnv) }
+}
+
+unsafe extern "system" fn get_string_utf_chars(
+    _: *mut sys::JNIEnv,
+    string: jstring,
+    is_copy: *mut jboolean,
+) -> *const c_char {
+    if !is_copy.is_null() {
+        unsafe { *is_copy = sys::JNI_FALSE };
+    }
+    match unsafe { object(string) } {
+        Some(FakeObject::String(string)) => string.as_ptr(),
+        _ => ptr::null(),
+    }
+}
+
+unsafe extern "system" fn release_string_utf_chars(
+    _: *mut sys::JNIEnv,
+    _: jstring,
+    _: *const c_char,
+) {
+}
+
+unsafe extern "system" fn get_string_utf_length(_: *mut sys::JNIEnv, string: jstring) -> jsize {
+    match unsafe { object(string) } {
+        Some(FakeObject::String(string)) => string.as_bytes().len() as jsize,
+        _ => 0,
+    }
+}
+
+unsafe extern "system" fn new_string_utf(env: *mut sys::JNIEnv, utf: *const c_char) -> jstring {
+    let string = CString::from(unsafe { CStr::from_ptr(utf) });
+    unsafe { fake_env(env) }.alloc(FakeObject::String(string))
+}
+
+unsafe extern "system" fn get_array_length(_: *mut sys::JNIEnv, array: jobject) -> jsize {
+    match unsafe { object(array) } {
+        Some(FakeObject::IntArray(array)) => array.borrow().len() as jsize,
+        Some(FakeObject::ObjectArray(array)) => array.borrow().len() as jsize,
+        _ => 0,
+    }
+}
+
+unsafe extern "system" fn new_int_array(env: *mut sys::JNIEnv, len: jsize) -> jintArray {
+    unsafe { fake_env(env) }.alloc(FakeObject::IntArray(RefCell::new(Vec::from_iter(
+        (0..len).map(|_| 0),
+    ))))
+}
+
+unsafe extern "system" fn get_int_array_region(
+    _: *mut sys::JNIEnv,
+    array: jintArray,
+    start: jsize,
+    len: jsize,
+    buf: *mut jint,
+) {
+    if let Some(FakeObject::IntArray(array)) = unsafe { object(array) } {
+        let array = array.borrow();
+        let region = &array[start as usize..(start + len) as usize];
+        unsafe { ptr::copy_nonoverlapping(region.as_ptr(), buf, region.len()) };
+    }
+}
+
+unsafe extern "system" fn set_int_array_region(
+    _: *mut sys::JNIEnv,
+    array: jintArray,
+    start: jsize,
+    len: jsize,
+    buf: *const jint,
+) {
+    if let Some(FakeObject::IntArray(array)) = unsafe { object(array) } {
+        let mut array = array.borrow_mut();
+        let region = &mut array[start as usize..(start + len) as usize];
+        unsafe { ptr::copy_nonoverlapping(buf, region.as_mut_ptr(), region.len()) };
+    }
+}
+
+unsafe extern "system" fn get_object_array_element(
+    _: *mut sys::JNIEnv,
+    array: jobjectArray,
+    index: jsize,
+) -> jobject {
+    match unsafe { object(array) } {
+        Some(FakeObject::ObjectArray(array)) => array.borrow()[index as usize],
+        _ => ptr::null_mut(),
+    }
+}
+
+unsafe extern "system" fn set_object_array_element(
+    _: *mut sys::JNIEnv,
+    array: jobjectArray,
+    index: jsize,
+    value: jobject,
+) {
+    if let Some(FakeObject::ObjectArray(array)) = unsafe { object(array) } {
+        array.borrow_mut()[index as usize] = value;
+    }
+}
+
+unsafe extern "system" fn delete_local_ref(_: *mut sys::JNIEnv, _: jobject) {}
+
+unsafe extern "system" fn exception_check(_: *mut sys::JNIEnv) -> jboolean {
+    sys::JNI_FALSE
+}
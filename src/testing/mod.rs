@@ -0,0 +1,895 @@
+//! An in-process mock of the Zygisk host, for unit-testing [`ZygiskModule`] implementations.
+//!
+//! [`MockHost`] builds a real API table for the chosen API version, backed by Rust functions that
+//! record every call and return scripted results. Modules are registered through the same
+//! [`ModuleAbi`] trampolines that Zygisk itself calls into, so the lifecycle callbacks can be
+//! driven on a plain Linux machine:
+//!
+//! ```
+//! use zygisk_api::{ZygiskModule, api::{V5, ZygiskApi}, testing::{Call, MockHost}};
+//! # use zygisk_api::api::v5::ZygiskOption;
+//!
+//! #[derive(Default)]
+//! struct MyModule;
+//!
+//! impl ZygiskModule for MyModule {
+//!     type Api = V5;
+//!     type State = ();
+//!
+//!     fn on_load(&self, mut api: ZygiskApi<'_, V5>, _: jni::JNIEnv<'_>) {
+//!         api.set_option(ZygiskOption::DlCloseModuleLibrary);
+//!     }
+//! }
+//!
+//! let mut host = MockHost::<V5>::new();
+//! assert!(host.load(MyModule));
+//! assert_eq!(
+//!     host.calls(),
+//!     [
+//!         Call::RegisterModule { api_version: 5 },
+//!         Call::SetOption(ZygiskOption::DlCloseModuleLibrary),
+//!     ]
+//! );
+//! ```
+//!
+//! Since most of the API table entries carry no context pointer, the host state is kept in a
+//! thread-local. Only one [`MockHost`] can be alive on a thread at any time.
+//!
+//! The [`JNIEnv`] handed to the module is backed by an empty function table unless one is supplied
+//! through [`MockHost::with_jni_env`], such as the one of a [`FakeJvm`]. Calling any JNI function
+//! on the empty table fails with [`jni::errors::Error::JNIEnvMethodNotFound`].
+
+use core::{cell::RefCell, ffi::CStr, mem, ptr::NonNull};
+use std::{
+    boxed::Box,
+    collections::VecDeque,
+    os::fd::{IntoRawFd, OwnedFd, RawFd},
+    rc::Rc,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use jni::{
+    JNIEnv,
+    sys::{JNINativeInterface_, JNINativeMethod},
+};
+use libc::{c_char, c_int, c_long, c_void, dev_t, ino_t};
+
+use crate::{
+    ZygiskModule,
+    api::{HasOptions, ZygiskApi, v1::ZygiskOption},
+    impl_sealing::Sealed,
+    raw::{
+        ApiTableRef, Dispatcher, Instance, ModuleAbi, ModuleAbiRef, ModuleDispatch, RawModule,
+        ZygiskRaw,
+    },
+};
+
+mod args;
+pub use args::{FakeAppArgs, FakeServerArgs};
+mod java;
+pub use java::FakeJvm;
+
+mod v1;
+mod v2;
+mod v3;
+mod v4;
+mod v5;
+
+/// A call made by a module into the mock host's API table
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Call {
+    RegisterModule {
+        api_version: c_long,
+    },
+    HookJniNativeMethods {
+        class_name: String,
+        /// `(name, signature)` of every method in the request, in order
+        methods: Vec<(String, String)>,
+    },
+    PltHookRegister {
+        regex: String,
+        symbol: String,
+    },
+    PltHookRegisterInode {
+        device: dev_t,
+        inode: ino_t,
+        symbol: String,
+    },
+    PltHookExclude {
+        regex: String,
+        symbol: String,
+    },
+    PltHookCommit,
+    ConnectCompanion,
+    SetOption(ZygiskOption),
+    GetModuleDir,
+    GetFlags,
+    ExemptFd(RawFd),
+}
+
+/// API versions that [`MockHost`] can build an API table for
+pub trait MockApi: for<'a> ZygiskRaw<'a> + Sealed + Copy {
+    #[doc(hidden)]
+    fn mock_table() -> <Self as ZygiskRaw<'static>>::ApiTable;
+
+    #[doc(hidden)]
+    fn app_args(fake: &mut FakeAppArgs) -> <Self as ZygiskRaw<'_>>::AppSpecializeArgs;
+}
+
+struct HostState {
+    calls: Vec<Call>,
+    max_api_version: c_long,
+    companions: VecDeque<OwnedFd>,
+    module_dir: Option<OwnedFd>,
+    flags: u32,
+    plt_hook_commit_result: bool,
+    exempt_fd_result: bool,
+    jni_originals: Vec<(String, String, String, *mut c_void)>,
+    plt_originals: Vec<(String, *const c_void)>,
+    pending_plt_hooks: Vec<(String, *mut *const c_void)>,
+    registered: Option<(c_long, *mut ())>,
+}
+
+impl Default for HostState {
+    fn default() -> Self {
+        Self {
+            calls: Vec::new(),
+            max_api_version: 5,
+            companions: VecDeque::new(),
+            module_dir: None,
+            flags: 0,
+            plt_hook_commit_result: true,
+            exempt_fd_result: true,
+            jni_originals: Vec::new(),
+            plt_originals: Vec::new(),
+            pending_plt_hooks: Vec::new(),
+            registered: None,
+        }
+    }
+}
+
+std::thread_local! {
+    static ACTIVE_HOST: RefCell<Option<Rc<RefCell<HostState>>>> = const { RefCell::new(None) };
+}
+
+fn with_state<R>(f: impl FnOnce(&mut HostState) -> R) -> R {
+    ACTIVE_HOST.with_borrow(|host| {
+        let host = host
+            .as_ref()
+            .expect("Zygisk API called without an active MockHost on this thread");
+        f(&mut host.borrow_mut())
+    })
+}
+
+fn record(call: Call) {
+    with_state(|state| state.calls.push(call));
+}
+
+unsafe fn string_from_ptr(ptr: *const c_char) -> String {
+    unsafe { CStr::from_ptr(ptr) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+struct LoadedModule<V>
+where
+    V: MockApi,
+{
+    abi: *mut ModuleAbi<'static, V>,
+    raw: *mut RawModule<'static, V>,
+    instance: *mut (dyn ModuleDispatch<V> + 'static),
+}
+
+impl<V> Drop for LoadedModule<V>
+where
+    V: MockApi,
+{
+    fn drop(&mut self) {
+        // The ABI struct borrows the raw module, which in turn borrows the module instance.
+        unsafe {
+            drop(Box::from_raw(self.abi));
+            drop(Box::from_raw(self.raw));
+            drop(Box::from_raw(self.instance));
+        }
+    }
+}
+
+/// A mock Zygisk host serving a single module through the `V` API table
+pub struct MockHost<V>
+where
+    V: MockApi,
+{
+    // Declared first so that the module is dropped before the table and JNI environment it refers to.
+    module: Option<LoadedModule<V>>,
+    state: Rc<RefCell<HostState>>,
+    table: Box<<V as ZygiskRaw<'static>>::ApiTable>,
+    _interface: Option<(Box<JNINativeInterface_>, Box<jni::sys::JNIEnv>)>,
+    env: *mut jni::sys::JNIEnv,
+}
+
+impl<V> MockHost<V>
+where
+    V: MockApi,
+{
+    /// Create a new mock host with an empty JNI function table.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another [`MockHost`] is alive on the current thread.
+    pub fn new() -> Self {
+        // SAFETY: every field of the function table is either a raw pointer or an `Option` of a function pointer.
+        let interface = Box::new(unsafe { mem::zeroed::<JNINativeInterface_>() });
+        let mut env = Box::new(&*interface as *const JNINativeInterface_);
+        let env_ptr: *mut jni::sys::JNIEnv = &mut *env;
+
+        Self::from_parts(Some((interface, env)), env_ptr)
+    }
+
+    /// Create a new mock host that hands `env` to the module.
+    ///
+    /// # Safety
+    ///
+    /// `env` must be a valid JNI environment for the current thread, and must outlive the host.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another [`MockHost`] is alive on the current thread.
+    pub unsafe fn with_jni_env(env: *mut jni::sys::JNIEnv) -> Self {
+        Self::from_parts(None, env)
+    }
+
+    fn from_parts(
+        interface: Option<(Box<JNINativeInterface_>, Box<jni::sys::JNIEnv>)>,
+        env: *mut jni::sys::JNIEnv,
+    ) -> Self {
+        let state = Rc::new(RefCell::new(HostState::default()));
+
+        ACTIVE_HOST.with_borrow_mut(|host| {
+            assert!(
+                host.is_none(),
+                "only one MockHost can be alive on a thread at a time"
+            );
+            *host = Some(Rc::clone(&state));
+        });
+
+        Self {
+            module: None,
+            state,
+            table: Box::new(V::mock_table()),
+            _interface: interface,
+            env,
+        }
+    }
+
+    /// Limit the API versions accepted by module registration, emulating an older Zygisk implementation.
+    pub fn set_max_api_version(&mut self, version: c_long) {
+        self.state.borrow_mut().max_api_version = version;
+    }
+
+    /// Queue a socket to be returned by the next companion connection.
+    ///
+    /// Connection attempts fail once the queue is exhausted.
+    pub fn push_companion(&mut self, sock: OwnedFd) {
+        self.state.borrow_mut().companions.push_back(sock);
+    }
+
+    /// Set the directory returned (as a duplicated file descriptor) by `get_module_dir`.
+    pub fn set_module_dir(&mut self, dir: OwnedFd) {
+        self.state.borrow_mut().module_dir = Some(dir);
+    }
+
+    /// Set the raw bits returned by `get_flags`.
+    pub fn set_flags(&mut self, flags: u32) {
+        self.state.borrow_mut().flags = flags;
+    }
+
+    /// Set whether `plt_hook_commit` succeeds. Defaults to `true`.
+    pub fn set_plt_hook_commit_result(&mut self, result: bool) {
+        self.state.borrow_mut().plt_hook_commit_result = result;
+    }
+
+    /// Set whether `exempt_fd` succeeds. Defaults to `true`.
+    pub fn set_exempt_fd_result(&mut self, result: bool) {
+        self.state.borrow_mut().exempt_fd_result = result;
+    }
+
+    /// Provide the original function pointer of a JNI native method.
+    ///
+    /// Hook requests for methods without a scripted original are treated as not found.
+    pub fn set_jni_original(
+        &mut self,
+        class_name: &str,
+        name: &str,
+        signature: &str,
+        original: *mut c_void,
+    ) {
+        self.state.borrow_mut().jni_originals.push((
+            class_name.to_string(),
+            name.to_string(),
+            signature.to_string(),
+            original,
+        ));
+    }
+
+    /// Provide the original function pointer written back to PLT hooks on `symbol` at commit time.
+    ///
+    /// Hooks on symbols without a scripted original are left untouched, as if the symbol was not found.
+    pub fn set_plt_original(&mut self, symbol: &str, original: *const c_void) {
+        self.state
+            .borrow_mut()
+            .plt_originals
+            .push((symbol.to_string(), original));
+    }
+
+    /// Returns every call recorded so far.
+    pub fn calls(&self) -> Vec<Call> {
+        self.state.borrow().calls.clone()
+    }
+
+    /// Returns and clears every call recorded so far.
+    pub fn take_calls(&mut self) -> Vec<Call> {
+        mem::take(&mut self.state.borrow_mut().calls)
+    }
+
+    /// Returns an API handle backed by this host's table.
+    pub fn api(&self) -> ZygiskApi<'_, V> {
+        ZygiskApi(unsafe { ApiTableRef::from_raw(&*self.table as *const _ as *const _) })
+    }
+
+    /// Returns the JNI environment handed to the module.
+    pub fn env(&self) -> JNIEnv<'_> {
+        unsafe { JNIEnv::from_raw(self.env).unwrap_unchecked() }
+    }
+
+    /// Register `module` with this host the same way [`crate::register_module!`] does, calling
+    /// [`ZygiskModule::on_load`] if the registration is accepted.
+    ///
+    /// Returns whether the registration was accepted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a module has already been registered.
+    pub fn load<M>(&mut self, module: M) -> bool
+    where
+        M: ZygiskModule<Api = V> + 'static,
+        for<'a> ZygiskApi<'a, V>: HasOptions,
+    {
+        assert!(
+            self.state.borrow().registered.is_none(),
+            "a module has already been registered"
+        );
+
+        let api_table: ApiTableRef<'static, V> =
+            unsafe { ApiTableRef::from_raw(&*self.table as *const _) };
+
+        let instance = Box::into_raw(Box::new(Dispatcher::new(module)));
+        let raw = Box::into_raw(Box::new(RawModule {
+            dispatch: unsafe { &*instance },
+            api_table,
+            jni_env: unsafe { JNIEnv::from_raw(self.env).unwrap_unchecked() },
+            untargeted: false,
+        }));
+        let abi = Box::into_raw(Box::new(V::abi_from_module(unsafe { &mut *raw })));
+
+        self.module = Some(LoadedModule {
+            abi,
+            raw,
+            instance: instance as *mut (dyn ModuleDispatch<V> + 'static),
+        });
+
+        let accepted =
+            unsafe { V::register_module_fn(api_table)(api_table, ModuleAbiRef::from_raw(abi)) };
+        if accepted {
+            unsafe { &mut *raw }.on_load();
+        }
+
+        accepted
+    }
+
+    /// Call a module entry point exported by [`crate::register_module!`] with this host's API table.
+    ///
+    /// Returns whether the module registered itself. The registered module can then be driven
+    /// through the lifecycle methods of the host, provided that it registered with the API
+    /// version of the host's table.
+    ///
+    /// # Safety
+    ///
+    /// `entry` must be an entry point generated by [`crate::register_module!`], and must not be
+    /// entered concurrently since it initializes the module in statics.
+    pub unsafe fn enter(
+        &mut self,
+        entry: unsafe extern "C" fn(*const (), *mut jni::sys::JNIEnv),
+    ) -> bool {
+        assert!(
+            self.state.borrow().registered.is_none(),
+            "a module has already been registered"
+        );
+
+        unsafe { entry(&*self.table as *const _ as *const (), self.env) };
+
+        self.state.borrow().registered.is_some()
+    }
+
+    fn loaded(&mut self) -> &mut ModuleAbi<'static, V> {
+        let (api_version, abi) = self
+            .state
+            .borrow()
+            .registered
+            .expect("no module has been registered with this MockHost");
+        assert_eq!(
+            api_version,
+            <V as ZygiskRaw<'static>>::API_VERSION,
+            "the module registered with a different API version than the host's table"
+        );
+
+        unsafe { &mut *(abi as *mut ModuleAbi<'static, V>) }
+    }
+
+    /// Invoke the module's `pre_app_specialize` callback through its ABI trampoline.
+    pub fn pre_app_specialize(&mut self, args: &mut <V as ZygiskRaw<'_>>::AppSpecializeArgs) {
+        let abi = self.loaded();
+        let args = args as *mut _ as *mut <V as ZygiskRaw<'static>>::AppSpecializeArgs;
+
+        (abi.pre_app_specialize_fn)(abi.this, unsafe { &mut *args });
+    }
+
+    /// Invoke the module's `post_app_specialize` callback through its ABI trampoline.
+    pub fn post_app_specialize(&mut self, args: &<V as ZygiskRaw<'_>>::AppSpecializeArgs) {
+        let abi = self.loaded();
+        let args = args as *const _ as *const <V as ZygiskRaw<'static>>::AppSpecializeArgs;
+
+        (abi.post_app_specialize_fn)(abi.this, unsafe { &*args });
+    }
+
+    /// Run the module through a whole app specialization, lending `fake` out in the layout of `V`
+    /// to both callbacks.
+    pub fn specialize_app(&mut self, fake: &mut FakeAppArgs) {
+        let mut args = V::app_args(fake);
+        self.pre_app_specialize(&mut args);
+        self.post_app_specialize(&args);
+    }
+
+    /// Invoke the module's `pre_server_specialize` callback through its ABI trampoline.
+    pub fn pre_server_specialize(&mut self, args: &mut <V as ZygiskRaw<'_>>::ServerSpecializeArgs) {
+        let abi = self.loaded();
+        let args = args as *mut _ as *mut <V as ZygiskRaw<'static>>::ServerSpecializeArgs;
+
+        (abi.pre_server_specialize_fn)(abi.this, unsafe { &mut *args });
+    }
+
+    /// Invoke the module's `post_server_specialize` callback through its ABI trampoline.
+    pub fn post_server_specialize(&mut self, args: &<V as ZygiskRaw<'_>>::ServerSpecializeArgs) {
+        let abi = self.loaded();
+        let args = args as *const _ as *const <V as ZygiskRaw<'static>>::ServerSpecializeArgs;
+
+        (abi.post_server_specialize_fn)(abi.this, unsafe { &*args });
+    }
+}
+
+/// A [`MockHost`] handing a [`FakeJvm`] to the module, along with the arguments of an app process
+#[cfg(test)]
+pub(crate) struct AppFixture<V>
+where
+    V: MockApi,
+{
+    // Declared first so that the host is dropped before the JVM it refers to.
+    pub host: MockHost<V>,
+    pub args: FakeAppArgs,
+    pub jvm: FakeJvm,
+}
+
+#[cfg(test)]
+impl<V> AppFixture<V>
+where
+    V: MockApi,
+{
+    /// Create the fixture for an app process named `nice_name`, running as uid 10123.
+    pub fn new(nice_name: &str) -> Self {
+        let jvm = FakeJvm::new();
+        // SAFETY: the JVM's environment is boxed, and is dropped after the host.
+        let host = unsafe { MockHost::with_jni_env(jvm.env_ptr()) };
+        let args = FakeAppArgs::new(&jvm, 10123, nice_name);
+
+        Self { host, args, jvm }
+    }
+
+    /// Run the loaded module through a whole app specialization.
+    pub fn specialize(&mut self) {
+        self.host.specialize_app(&mut self.args);
+    }
+}
+
+impl<V> Default for MockHost<V>
+where
+    V: MockApi,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> Drop for MockHost<V>
+where
+    V: MockApi,
+{
+    fn drop(&mut self) {
+        self.module = None;
+        ACTIVE_HOST.with_borrow_mut(|host| *host = None);
+    }
+}
+
+// Table entries shared by every API version.
+
+fn register_module(api_version: c_long, abi: *mut ()) -> bool {
+    with_state(|state| {
+        state.calls.push(Call::RegisterModule { api_version });
+
+        let accepted = state.registered.is_none() && api_version <= state.max_api_version;
+        if accepted {
+            state.registered = Some((api_version, abi));
+        }
+        accepted
+    })
+}
+
+extern "C" fn hook_jni_native_methods(
+    _: JNIEnv<'_>,
+    class_name: *const c_char,
+    methods: NonNull<JNINativeMethod>,
+    count: c_int,
+) {
+    let class_name = unsafe { string_from_ptr(class_name) };
+    let methods = unsafe { core::slice::from_raw_parts_mut(methods.as_ptr(), count as usize) };
+
+    let requested = methods
+        .iter()
+        .map(|method| unsafe {
+            (
+                string_from_ptr(method.name),
+                string_from_ptr(method.signature),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    with_state(|state| {
+        for (method, (name, signature)) in methods.iter_mut().zip(&requested) {
+            method.fnPtr = state
+                .jni_originals
+                .iter()
+                .find(|(c, n, s, _)| *c == class_name && n == name && s == signature)
+                .map_or(core::ptr::null_mut(), |(.., original)| *original);
+        }
+
+        state.calls.push(Call::HookJniNativeMethods {
+            class_name,
+            methods: requested,
+        });
+    });
+}
+
+unsafe extern "C" fn plt_hook_register(
+    regex: *const c_char,
+    symbol: *const c_char,
+    _: *const c_void,
+    original: &mut *const c_void,
+) {
+    let regex = unsafe { string_from_ptr(regex) };
+    let symbol = unsafe { string_from_ptr(symbol) };
+
+    with_state(|state| {
+        state.pending_plt_hooks.push((symbol.clone(), original));
+        state.calls.push(Call::PltHookRegister { regex, symbol });
+    });
+}
+
+unsafe extern "C" fn plt_hook_register_inode(
+    device: dev_t,
+    inode: ino_t,
+    symbol: *const c_char,
+    _: *const c_void,
+    original: &mut *const c_void,
+) {
+    let symbol = unsafe { string_from_ptr(symbol) };
+
+    with_state(|state| {
+        state.pending_plt_hooks.push((symbol.clone(), original));
+        state.calls.push(Call::PltHookRegisterInode {
+            device,
+            inode,
+            symbol,
+        });
+    });
+}
+
+unsafe extern "C" fn plt_hook_exclude(regex: *const c_char, symbol: *const c_char) {
+    record(Call::PltHookExclude {
+        regex: unsafe { string_from_ptr(regex) },
+        symbol: unsafe { string_from_ptr(symbol) },
+    });
+}
+
+extern "C" fn plt_hook_commit() -> bool {
+    with_state(|state| {
+        state.calls.push(Call::PltHookCommit);
+
+        let pending = mem::take(&mut state.pending_plt_hooks);
+        if !state.plt_hook_commit_result {
+            return false;
+        }
+
+        for (symbol, original) in pending {
+            if let Some((_, ptr)) = state.plt_originals.iter().find(|(s, _)| *s == symbol) {
+                unsafe { *original = *ptr };
+            }
+        }
+        true
+    })
+}
+
+extern "C" fn connect_companion(_: NonNull<Instance>) -> c_int {
+    with_state(|state| {
+        state.calls.push(Call::ConnectCompanion);
+        state
+            .companions
+            .pop_front()
+            .map_or(-1, IntoRawFd::into_raw_fd)
+    })
+}
+
+extern "C" fn set_option(_: NonNull<Instance>, option: ZygiskOption) {
+    record(Call::SetOption(option));
+}
+
+extern "C" fn get_module_dir(_: NonNull<Instance>) -> c_int {
+    with_state(|state| {
+        state.calls.push(Call::GetModuleDir);
+        state.module_dir.as_ref().map_or(-1, |dir| {
+            use std::os::fd::AsRawFd;
+            unsafe { libc::dup(dir.as_raw_fd()) }
+        })
+    })
+}
+
+extern "C" fn get_flags(_: NonNull<Instance>) -> u32 {
+    with_state(|state| {
+        state.calls.push(Call::GetFlags);
+        state.flags
+    })
+}
+
+extern "C" fn exempt_fd(fd: c_int) -> bool {
+    with_state(|state| {
+        state.calls.push(Call::ExemptFd(fd));
+        state.exempt_fd_result
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::{Cell, RefCell};
+    use std::{
+        io::{Read, Write},
+        os::unix::net::UnixStream,
+        rc::Rc,
+        vec::Vec,
+    };
+
+    use jni::{
+        JNIEnv,
+        strings::JNIString,
+        sys::{JNINativeMethod, jintArray},
+    };
+
+    use super::{AppFixture, Call, MockHost};
+    use crate::{
+        ZygiskModule,
+        api::{
+            V1, V4, V5, ZygiskApi,
+            v4::{ServerSpecializeArgs, StateFlags, ZygiskOption},
+        },
+        args::AnyAppSpecializeArgs,
+        raw::ZygiskRaw,
+    };
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Rc<RefCell<Vec<&'static str>>>,
+    }
+
+    impl ZygiskModule for Recorder {
+        type Api = V4;
+        type State = ();
+
+        fn on_load(&self, _: ZygiskApi<'_, V4>, _: JNIEnv<'_>) {
+            self.events.borrow_mut().push("on_load");
+        }
+
+        fn pre_server_specialize<'a>(
+            &self,
+            mut api: ZygiskApi<'a, V4>,
+            _: JNIEnv<'a>,
+            args: &'a mut ServerSpecializeArgs<'a>,
+            _: &mut (),
+        ) {
+            self.events.borrow_mut().push("pre_server_specialize");
+            *args.uid = 1000;
+
+            if api
+                .get_flags()
+                .unwrap()
+                .contains(StateFlags::PROCESS_ON_DENYLIST)
+            {
+                api.set_option(ZygiskOption::DlCloseModuleLibrary);
+            }
+
+            api.with_companion(|stream| stream.write_all(b"ping").unwrap())
+                .unwrap();
+        }
+
+        fn post_server_specialize<'a>(
+            &self,
+            _: ZygiskApi<'a, V4>,
+            _: JNIEnv<'a>,
+            _: &'a ServerSpecializeArgs<'a>,
+            _: (),
+        ) {
+            self.events.borrow_mut().push("post_server_specialize");
+        }
+    }
+
+    #[test]
+    fn drives_server_specialization() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let (sock, mut peer) = UnixStream::pair().unwrap();
+
+        let mut host = MockHost::<V4>::new();
+        host.set_flags(StateFlags::PROCESS_ON_DENYLIST.bits());
+        host.push_companion(sock.into());
+
+        assert!(host.load(Recorder {
+            events: Rc::clone(&events),
+        }));
+
+        let (mut uid, mut gid, mut gids): (_, _, jintArray) = (0, 0, core::ptr::null_mut());
+        let (mut runtime_flags, mut permitted, mut effective) = (0, 0, 0);
+        let mut args = ServerSpecializeArgs {
+            uid: &mut uid,
+            gid: &mut gid,
+            gids: &mut gids,
+            runtime_flags: &mut runtime_flags,
+            permitted_capabilities: &mut permitted,
+            effective_capabilities: &mut effective,
+        };
+
+        host.pre_server_specialize(&mut args);
+        host.post_server_specialize(&args);
+
+        assert_eq!(uid, 1000);
+        assert_eq!(
+            *events.borrow(),
+            ["on_load", "pre_server_specialize", "post_server_specialize"]
+        );
+        assert_eq!(
+            host.calls(),
+            [
+                Call::RegisterModule { api_version: 4 },
+                Call::GetFlags,
+                Call::SetOption(ZygiskOption::DlCloseModuleLibrary),
+                Call::ConnectCompanion,
+            ]
+        );
+
+        let mut buf = [0; 4];
+        peer.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ping");
+    }
+
+    /// Remembers the uid that zygote asked for, and reports it after specialization
+    #[derive(Default)]
+    struct Renamer {
+        original_uid: Rc<Cell<Option<i32>>>,
+    }
+
+    impl ZygiskModule for Renamer {
+        type Api = V5;
+        type State = Option<i32>;
+
+        fn pre_app_specialize<'a>(
+            &self,
+            _: ZygiskApi<'a, V5>,
+            _: JNIEnv<'a>,
+            args: &'a mut <V5 as ZygiskRaw<'a>>::AppSpecializeArgs,
+            state: &mut Option<i32>,
+        ) {
+            *state = Some(args.uid());
+            args.set_uid(10999);
+        }
+
+        fn post_app_specialize<'a>(
+            &self,
+            _: ZygiskApi<'a, V5>,
+            _: JNIEnv<'a>,
+            _: &'a <V5 as ZygiskRaw<'a>>::AppSpecializeArgs,
+            state: Option<i32>,
+        ) {
+            self.original_uid.set(state);
+        }
+    }
+
+    #[test]
+    fn carries_state_to_post_callbacks() {
+        let mut fixture = AppFixture::<V5>::new("com.example");
+        let original_uid = Rc::new(Cell::new(None));
+
+        assert!(fixture.host.load(Renamer {
+            original_uid: Rc::clone(&original_uid),
+        }));
+        fixture.specialize();
+
+        assert_eq!(fixture.args.uid, 10999);
+        assert_eq!(original_uid.get(), Some(10123));
+    }
+
+    #[test]
+    fn rejects_newer_api_versions() {
+        let mut host = MockHost::<V4>::new();
+        host.set_max_api_version(3);
+
+        assert!(!host.load(Recorder::default()));
+        assert_eq!(host.calls(), [Call::RegisterModule { api_version: 4 }]);
+    }
+
+    #[test]
+    fn scripts_hook_results() {
+        let mut host = MockHost::<V1>::new();
+        host.set_plt_original("open", 0x1234 as *const _);
+        host.set_jni_original(
+            "android/os/Process",
+            "setArgV0",
+            "(Ljava/lang/String;)V",
+            0x5678 as *mut _,
+        );
+
+        let mut api = host.api();
+
+        let mut open = core::ptr::null();
+        let mut close = core::ptr::null();
+        unsafe {
+            api.plt_hook_register(c".*libc\\.so$", c"open", 0x1000 as *const (), &mut open);
+            api.plt_hook_register(c".*libc\\.so$", c"close", 0x2000 as *const (), &mut close);
+        }
+        assert!(api.plt_hook_commit().is_ok());
+        assert_eq!((open as usize, close as usize), (0x1234, 0));
+
+        let (class_name, names, signatures) = (
+            JNIString::from("android/os/Process"),
+            [
+                JNIString::from("setArgV0"),
+                JNIString::from("getUidForName"),
+            ],
+            [
+                JNIString::from("(Ljava/lang/String;)V"),
+                JNIString::from("(Ljava/lang/String;)I"),
+            ],
+        );
+        let mut methods = names
+            .iter()
+            .zip(&signatures)
+            .map(|(name, signature)| JNINativeMethod {
+                name: name.as_ptr() as *mut _,
+                signature: signature.as_ptr() as *mut _,
+                fnPtr: 0x1000 as *mut _,
+            })
+            .collect::<Vec<_>>();
+
+        let env = host.env();
+        unsafe {
+            host.api()
+                .hook_jni_native_methods(env, class_name, &mut methods)
+        };
+
+        assert_eq!(methods[0].fnPtr as usize, 0x5678);
+        assert!(methods[1].fnPtr.is_null());
+    }
+}
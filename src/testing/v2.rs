@@ -0,0 +1,35 @@
+use core::ptr::NonNull;
+
+use crate::{
+    api::{V2, v1},
+    raw::{ApiTableRef, BaseApi, ModuleAbiRef, v2::ApiTable},
+};
+
+use super::{FakeAppArgs, MockApi};
+
+unsafe extern "C" fn register_module(_: ApiTableRef<'_, V2>, abi: ModuleAbiRef<'_, V2>) -> bool {
+    super::register_module(unsafe { (*abi.0).api_version }, abi.0.cast())
+}
+
+impl MockApi for V2 {
+    fn app_args(fake: &mut FakeAppArgs) -> v1::AppSpecializeArgs<'_> {
+        fake.v1()
+    }
+
+    fn mock_table() -> ApiTable {
+        ApiTable {
+            base: BaseApi {
+                this: NonNull::dangling(),
+                register_module_fn: register_module,
+            },
+            hook_jni_native_methods_fn: super::hook_jni_native_methods,
+            plt_hook_register_fn: super::plt_hook_register,
+            plt_hook_exclude_fn: super::plt_hook_exclude,
+            plt_hook_commit_fn: super::plt_hook_commit,
+            connect_companion_fn: super::connect_companion,
+            set_option_fn: super::set_option,
+            get_module_dir_fn: super::get_module_dir,
+            get_flags_fn: super::get_flags,
+        }
+    }
+}
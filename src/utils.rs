@@ -2,6 +2,7 @@ use core::mem;
 
 pub struct ShapeAssertion<T, U>(T, U);
 impl<T, U> ShapeAssertion<T, U> {
+    #[allow(clippy::manual_is_multiple_of)]
     pub const ASSERT: () = {
         assert!(mem::size_of::<T>() == mem::size_of::<U>(), "size mismatch");
         assert!(
@@ -10,3 +11,47 @@ impl<T, U> ShapeAssertion<T, U> {
         );
     };
 }
+
+/// Implements a function pointer trait for the safe and unsafe function pointers with the `$abi`
+/// calling convention, of up to twelve arguments. The trait must have the `into_ptr` and
+/// `from_ptr` methods; `variadic` also covers the C variadic pointers, such as the type of `open`.
+macro_rules! impl_fn_ptr {
+    ($trait:ident, $abi:literal) => {
+        $crate::utils::impl_fn_ptr!(@each $trait, $abi, none, [A, B, C, D, E, F, G, H, I, J, K, L]);
+    };
+    ($trait:ident, $abi:literal, variadic) => {
+        $crate::utils::impl_fn_ptr!(@each $trait, $abi, variadic, [A, B, C, D, E, F, G, H, I, J, K, L]);
+    };
+    (@each $trait:ident, $abi:literal, $varargs:ident, []) => {
+        $crate::utils::impl_fn_ptr!(@fixed $trait, $abi, []);
+    };
+    (@each $trait:ident, $abi:literal, $varargs:ident, [$first:ident $(, $rest:ident)*]) => {
+        $crate::utils::impl_fn_ptr!(@fixed $trait, $abi, [$first $(, $rest)*]);
+        $crate::utils::impl_fn_ptr!(@$varargs $trait, $abi, [$first $(, $rest)*]);
+        $crate::utils::impl_fn_ptr!(@each $trait, $abi, $varargs, [$($rest),*]);
+    };
+    (@fixed $trait:ident, $abi:literal, [$($arg:ident),*]) => {
+        $crate::utils::impl_fn_ptr!(@one $trait, extern $abi fn($($arg),*) -> Ret, [$($arg),*]);
+        $crate::utils::impl_fn_ptr!(@one $trait, unsafe extern $abi fn($($arg),*) -> Ret, [$($arg),*]);
+    };
+    (@none $trait:ident, $abi:literal, [$($arg:ident),*]) => {};
+    (@variadic $trait:ident, $abi:literal, [$($arg:ident),*]) => {
+        $crate::utils::impl_fn_ptr!(@one $trait, unsafe extern $abi fn($($arg,)* ...) -> Ret, [$($arg),*]);
+    };
+    (@one $trait:ident, $ty:ty, [$($arg:ident),*]) => {
+        impl<Ret, $($arg),*> $crate::impl_sealing::Sealed for $ty {}
+
+        impl<Ret, $($arg),*> $trait for $ty {
+            #[inline(always)]
+            fn into_ptr(self) -> *const () {
+                self as *const ()
+            }
+
+            #[inline(always)]
+            unsafe fn from_ptr(ptr: *const ()) -> Self {
+                unsafe { ::core::mem::transmute::<*const (), Self>(ptr) }
+            }
+        }
+    };
+}
+pub(crate) use impl_fn_ptr;